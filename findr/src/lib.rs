@@ -1,7 +1,8 @@
 use std::error::Error;
 
 use clap::{App, Arg};
-use regex::Regex;
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
 use walkdir::WalkDir;
 
 use crate::EntryType::*;
@@ -18,8 +19,19 @@ enum EntryType {
 #[derive(Debug)]
 pub struct Config {
     paths: Vec<String>,
-    names: Vec<Regex>,
+    /// `--name` and `--iname` globs, matched against the whole file name.
+    names: Vec<GlobMatcher>,
+    /// `--path` and `--ipath` globs, matched against the whole path.
+    path_globs: Vec<GlobMatcher>,
+    /// `--regex` and `--iregex`, matched against the whole path.
+    regexes: Vec<Regex>,
+    /// `--name-regex`, matched anywhere in the file name.
+    name_regexes: Vec<Regex>,
     entry_types: Vec<EntryType>,
+    /// Deepest level to descend to; the search paths are at depth 0.
+    max_depth: Option<usize>,
+    /// Shallowest level to print.
+    min_depth: Option<usize>,
 }
 
 
@@ -32,7 +44,15 @@ pub fn run(config: Config) -> MyResult<()> {
     // println!("########################");
     
     for path in config.paths {
-        for entry in WalkDir::new(path) {
+        let mut walk_dir = WalkDir::new(path);
+        if let Some(max_depth) = config.max_depth {
+            walk_dir = walk_dir.max_depth(max_depth);
+        }
+        if let Some(min_depth) = config.min_depth {
+            walk_dir = walk_dir.min_depth(min_depth);
+        }
+        
+        for entry in walk_dir {
             match entry {
                 Err(err) => eprintln!("{}", err),
                 Ok(entry) => {
@@ -48,14 +68,26 @@ pub fn run(config: Config) -> MyResult<()> {
                             is_match
                         }) { true } else { false };
                         
-                    let match_re = if config.names.is_empty() ||
-                        config.names.iter().any(|re| {
-                            let is_match = re.is_match(&entry.file_name().to_string_lossy());
-                            // println!("name is_match: {} = {}", entry.file_name().to_string_lossy(), is_match);
+                    let file_name = entry.file_name().to_string_lossy();
+                    let path = entry.path().to_string_lossy();
+                    
+                    let match_name = config.names.is_empty() ||
+                        config.names.iter().any(|glob| glob.is_match(file_name.as_ref()));
+                    
+                    let match_path = config.path_globs.is_empty() ||
+                        config.path_globs.iter().any(|glob| glob.is_match(path.as_ref()));
+                    
+                    let match_regex = config.regexes.is_empty() ||
+                        config.regexes.iter().any(|re| re.is_match(&path));
+                    
+                    let match_re = config.name_regexes.is_empty() ||
+                        config.name_regexes.iter().any(|re| {
+                            let is_match = re.is_match(&file_name);
+                            // println!("name is_match: {} = {}", file_name, is_match);
                             is_match
-                        }) { true } else { false };
+                        });
                     
-                    if match_entry_type && match_re {
+                    if match_entry_type && match_name && match_path && match_regex && match_re {
                         println!("{}", entry.path().display());
                     }
                     
@@ -82,10 +114,58 @@ pub fn get_args() -> MyResult<Config> {
         )
         .arg(
             Arg::with_name("names")
-                .value_name("NAME")
+                .value_name("GLOB")
                 .short("n")
                 .long("name")
-                .help("Name")
+                .help("File name matching the shell pattern GLOB")
+                .takes_value(true)
+                .multiple(true)
+        )
+        .arg(
+            Arg::with_name("inames")
+                .value_name("GLOB")
+                .long("iname")
+                .help("Like --name, ignoring case")
+                .takes_value(true)
+                .multiple(true)
+        )
+        .arg(
+            Arg::with_name("path_globs")
+                .value_name("GLOB")
+                .long("path")
+                .help("Whole path matching the shell pattern GLOB")
+                .takes_value(true)
+                .multiple(true)
+        )
+        .arg(
+            Arg::with_name("ipath_globs")
+                .value_name("GLOB")
+                .long("ipath")
+                .help("Like --path, ignoring case")
+                .takes_value(true)
+                .multiple(true)
+        )
+        .arg(
+            Arg::with_name("regexes")
+                .value_name("REGEX")
+                .long("regex")
+                .help("Whole path matching REGEX")
+                .takes_value(true)
+                .multiple(true)
+        )
+        .arg(
+            Arg::with_name("iregexes")
+                .value_name("REGEX")
+                .long("iregex")
+                .help("Like --regex, ignoring case")
+                .takes_value(true)
+                .multiple(true)
+        )
+        .arg(
+            Arg::with_name("name_regexes")
+                .value_name("REGEX")
+                .long("name-regex")
+                .help("File name containing a match of REGEX")
                 .takes_value(true)
                 .multiple(true)
         )
@@ -99,15 +179,38 @@ pub fn get_args() -> MyResult<Config> {
                 .takes_value(true)
                 .multiple(true)
         )
+        .arg(
+            Arg::with_name("max_depth")
+                .value_name("LEVELS")
+                .long("max-depth")
+                .help("Descend at most LEVELS below the search paths")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("min_depth")
+                .value_name("LEVELS")
+                .long("min-depth")
+                .help("Skip entries less than LEVELS below the search paths")
+                .takes_value(true)
+        )
         .get_matches();
     
     let paths = matches.values_of_lossy("paths").unwrap();
     
-    let names = matches
-        .values_of_lossy("names")
+    let mut names = build_globs(&matches, "names", "--name", false)?;
+    names.extend(build_globs(&matches, "inames", "--iname", true)?);
+    
+    let mut path_globs = build_globs(&matches, "path_globs", "--path", false)?;
+    path_globs.extend(build_globs(&matches, "ipath_globs", "--ipath", true)?);
+    
+    let mut regexes = build_regexes(&matches, "regexes", "--regex", false)?;
+    regexes.extend(build_regexes(&matches, "iregexes", "--iregex", true)?);
+    
+    let name_regexes = matches
+        .values_of_lossy("name_regexes")
         .map(|vals| {
             vals.into_iter().map(|name| {
-                Regex::new(&name).map_err(|_| format!("Invalid --name \"{}\"", name))
+                Regex::new(&name).map_err(|_| format!("Invalid --name-regex \"{}\"", name))
             })
             .collect::<Result<Vec<_>, _>>()
         })
@@ -128,15 +231,69 @@ pub fn get_args() -> MyResult<Config> {
         })
         .unwrap_or_default();
     
+    let max_depth = matches
+        .value_of("max_depth")
+        .map(|val| parse_depth(val, "--max-depth"))
+        .transpose()?;
+    
+    let min_depth = matches
+        .value_of("min_depth")
+        .map(|val| parse_depth(val, "--min-depth"))
+        .transpose()?;
+    
+    if let (Some(min), Some(max)) = (min_depth, max_depth) && min > max {
+        return Err(format!("--min-depth {} is greater than --max-depth {}", min, max).into());
+    }
+    
     Ok(Config {
         paths,
         names,
+        path_globs,
+        regexes,
+        name_regexes,
         entry_types,
+        max_depth,
+        min_depth,
     })
 }
 
+fn parse_depth(val: &str, option: &str) -> MyResult<usize> {
+    val.parse().map_err(|_| format!("Invalid {} \"{}\"", option, val).into())
+}
+
+/// Builds the values of a glob option. A `*` also matches `/`, as in find.
+fn build_globs(matches: &clap::ArgMatches, name: &str, option: &str, ignore_case: bool) -> MyResult<Vec<GlobMatcher>> {
+    matches
+        .values_of_lossy(name)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|glob| {
+            GlobBuilder::new(&glob)
+                .case_insensitive(ignore_case)
+                .build()
+                .map(|glob| glob.compile_matcher())
+                .map_err(|_| format!("Invalid {} \"{}\"", option, glob).into())
+        })
+        .collect()
+}
+
+/// Builds the values of a regex option, anchored to match the whole path.
+fn build_regexes(matches: &clap::ArgMatches, name: &str, option: &str, ignore_case: bool) -> MyResult<Vec<Regex>> {
+    matches
+        .values_of_lossy(name)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|re| {
+            RegexBuilder::new(&format!("^(?:{})$", re))
+                .case_insensitive(ignore_case)
+                .build()
+                .map_err(|_| format!("Invalid {} \"{}\"", option, re).into())
+        })
+        .collect()
+}
+
 #[test]
-fn test() -> () {
+fn test() {
     // let v1 = vec![Ok(1), Err(9), Ok(3)];
     // let v2 = v1.iter().map(|r| {
     //     r.map_err(|n| { format!("error: {}", n) } )
@@ -44,7 +44,7 @@ fn run(args: &[&str], expected_file: &str) -> TestResult {
 #[test]
 fn dies_bad_type() -> TestResult {
     Command::new(cargo::cargo_bin!())
-        .args(&["--type", "x"])
+        .args(["--type", "x"])
         .assert()
         .failure()
         .stderr(predicates::str::contains("error: 'x' isn't a valid value for '--type <TYPE>...'"));
@@ -55,10 +55,54 @@ fn dies_bad_type() -> TestResult {
 #[test]
 fn dies_bad_name() -> TestResult {
     Command::new(cargo::cargo_bin!())
-        .args(&["--name", "*.csv"])
+        .args(["--name", "a[b"])
         .assert()
         .failure()
-        .stderr(predicates::str::contains("Invalid --name \"*.csv\""));
+        .stderr(predicates::str::contains("Invalid --name \"a[b\""));
+        
+    Ok(())
+}
+
+#[test]
+fn dies_bad_name_regex() -> TestResult {
+    Command::new(cargo::cargo_bin!())
+        .args(["--name-regex", "*.csv"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Invalid --name-regex \"*.csv\""));
+        
+    Ok(())
+}
+
+#[test]
+fn dies_bad_regex() -> TestResult {
+    Command::new(cargo::cargo_bin!())
+        .args(["--regex", "*.csv"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Invalid --regex \"*.csv\""));
+        
+    Ok(())
+}
+
+#[test]
+fn dies_bad_max_depth() -> TestResult {
+    Command::new(cargo::cargo_bin!())
+        .args(["--max-depth", "x"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Invalid --max-depth \"x\""));
+        
+    Ok(())
+}
+
+#[test]
+fn dies_min_depth_over_max_depth() -> TestResult {
+    Command::new(cargo::cargo_bin!())
+        .args(["--min-depth", "2", "--max-depth", "1"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("--min-depth 2 is greater than --max-depth 1"));
         
     Ok(())
 }
@@ -69,7 +113,7 @@ fn skips_bad_dir() -> TestResult {
     let expected = format!("IO error for operation on {}: No such file or directory (os error 2)", bad_file);
     
     Command::new(cargo::cargo_bin!())
-        .args(&[&bad_file])
+        .args([&bad_file])
         .assert()
         .success()
         .stderr(predicates::str::contains(expected));
@@ -85,7 +129,87 @@ fn name_a() -> TestResult {
     run(&["tests/inputs/a", "-n", "a"], "tests/expected/name_a.txt")
 }
 
+#[test]
+fn name_regex_a() -> TestResult {
+    run(&["tests/inputs/a", "--name-regex", "a"], "tests/expected/name_regex_a.txt")
+}
+
+#[test]
+fn short_name_csv() -> TestResult {
+    run(&["tests/inputs", "-n", "*.csv"], "tests/expected/name_csv.txt")
+}
+
+#[test]
+fn short_name_is_anchored() -> TestResult {
+    run(&["tests/inputs", "-n", "csv"], "tests/expected/empty.txt")
+}
+
 #[test]
 fn path_a() -> TestResult {
     run(&["tests/inputs/a"], "tests/expected/path_a.txt")
 }
+
+#[test]
+fn name_csv() -> TestResult {
+    run(&["tests/inputs", "--name", "*.csv"], "tests/expected/name_csv.txt")
+}
+
+#[test]
+fn name_csv_mp3() -> TestResult {
+    run(&["tests/inputs", "--name", "*.csv", "--name", "*.mp3"], "tests/expected/name_csv_mp3.txt")
+}
+
+#[test]
+fn name_is_anchored() -> TestResult {
+    run(&["tests/inputs", "--name", "csv"], "tests/expected/empty.txt")
+}
+
+#[test]
+fn iname_csv() -> TestResult {
+    run(&["tests/inputs", "--iname", "*.CSV"], "tests/expected/name_csv.txt")
+}
+
+#[test]
+fn name_txt_path_a_d() -> TestResult {
+    run(&["tests/inputs/a", "tests/inputs/d", "--name", "*.txt"], "tests/expected/name_txt_path_a_d.txt")
+}
+
+#[test]
+fn path_glob_b() -> TestResult {
+    run(&["tests/inputs", "--path", "*/a/b/*"], "tests/expected/path_glob_b.txt")
+}
+
+#[test]
+fn ipath_glob_b() -> TestResult {
+    run(&["tests/inputs", "--ipath", "*/A/B/*"], "tests/expected/path_glob_b.txt")
+}
+
+#[test]
+fn regex_d() -> TestResult {
+    run(&["tests/inputs", "--regex", r".*/d/d\.t.."], "tests/expected/regex_d.txt")
+}
+
+#[test]
+fn regex_is_anchored() -> TestResult {
+    run(&["tests/inputs", "--regex", "csv"], "tests/expected/empty.txt")
+}
+
+#[test]
+fn iregex_csv() -> TestResult {
+    run(&["tests/inputs", "--iregex", r".*\.CSV"], "tests/expected/name_csv.txt")
+}
+
+#[test]
+fn max_depth_1() -> TestResult {
+    run(&["tests/inputs", "--max-depth", "1"], "tests/expected/max_depth_1.txt")
+}
+
+#[test]
+fn min_depth_2() -> TestResult {
+    run(&["tests/inputs/a", "--min-depth", "2"], "tests/expected/min_depth_2.txt")
+}
+
+#[test]
+fn depth_1_type_d() -> TestResult {
+    run(&["tests/inputs", "--min-depth", "1", "--max-depth", "1", "-t", "d"], "tests/expected/depth_1_type_d.txt")
+}
@@ -1,28 +1,194 @@
-use std::{error::Error, io::{self, BufRead, BufReader, Write}};
+use std::{
+    borrow::Cow,
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, hash_map::DefaultHasher},
+    error::Error,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+};
 
 use clap::Arg;
+use regex::bytes::Regex;
+use unicase::UniCase;
 
+#[allow(non_snake_case)]
 pub mod Const {
     pub const PRG: &str = "uniqr";
-    pub const ARG_IN_FILE: &str = "in_file";
-    pub const ARG_OUT_FILE: &str = "out_file";
+    pub const ARG_FILES: &str = "files";
+    pub const ARG_OUTPUT: &str = "output";
+    pub const ARG_IN_PLACE: &str = "in_place";
     pub const ARG_COUNT: &str = "count";
+    pub const ARG_REPEATED: &str = "repeated";
+    pub const ARG_UNIQUE: &str = "unique";
+    pub const ARG_ALL_REPEATED: &str = "all_repeated";
+    pub const ARG_IGNORE_CASE: &str = "ignore_case";
+    pub const ARG_SKIP_FIELDS: &str = "skip_fields";
+    pub const ARG_SKIP_CHARS: &str = "skip_chars";
+    pub const ARG_CHECK_CHARS: &str = "check_chars";
+    pub const ARG_GLOBAL: &str = "global";
+    pub const ARG_MAX_MEMORY: &str = "max_memory";
+    pub const ARG_TOP: &str = "top";
+    pub const ARG_SORT: &str = "sort";
+    pub const ARG_PERCENT: &str = "percent";
+    pub const ARG_CUMULATIVE: &str = "cumulative";
+    pub const ARG_MASK: &str = "mask";
+    pub const ARG_MASK_REGEX: &str = "mask_regex";
+    pub const ARG_KEY_FIELD: &str = "key_field";
+    pub const ARG_DELIMITER: &str = "delimiter";
+    pub const ARG_CSV: &str = "csv";
+    pub const ARG_KEY_REGEX: &str = "key_regex";
+    pub const ARG_KEEP: &str = "keep";
+    pub const ARG_GROUP: &str = "group";
+    pub const ARG_FORMAT: &str = "format";
+    pub const ARG_ZERO_TERMINATED: &str = "zero_terminated";
+    pub const ARG_SIMILARITY: &str = "similarity";
+    pub const ARG_METRIC: &str = "metric";
 }
 
 use Const::*;
 
 pub type MyResult<T> = Result<T, Box<dyn Error>>;
 
+/// Number of hashed partitions `--global` spreads the input over once the
+/// seen lines no longer fit in memory.
+const NUM_PARTITIONS: usize = 256;
+
+/// Sequence number of the partition records that only mark a line as seen.
+const SEEN_MARKER: u64 = u64::MAX;
+
 #[derive(Debug)]
 pub struct Config {
-    in_file: String,
+    in_files: Vec<String>,
     out_file: Option<String>,
+    in_place: bool,
     count: bool,
+    repeated: bool,
+    unique: bool,
+    all_repeated: Option<Delimit>,
+    ignore_case: bool,
+    skip_fields: usize,
+    skip_chars: usize,
+    check_chars: Option<usize>,
+    global: bool,
+    max_memory: usize,
+    top: Option<usize>,
+    sort: Option<SortBy>,
+    percent: bool,
+    cumulative: bool,
+    masks: Vec<Mask>,
+    key_field: Option<usize>,
+    delimiter: Option<char>,
+    csv: bool,
+    key_regex: Option<Regex>,
+    keep_last: bool,
+    group: Option<Delimit>,
+    format: OutputFormat,
+    /// `\n`, or `\0` for `-z`.
+    terminator: u8,
+    similarity: Option<f64>,
+    metric: Metric,
+}
+
+/// How groups are delimited with `-D` and `--group`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delimit {
+    None,
+    Prepend,
+    Separate,
+    Append,
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    /// A single JSON array of records.
+    Json,
+    /// One JSON object per line.
+    Jsonl,
+    Csv,
+}
+
+/// Replaces the tokens matched by `regex` with `placeholder` before lines are
+/// compared.
+#[derive(Debug)]
+struct Mask {
+    regex: Regex,
+    placeholder: &'static str,
+}
+
+/// The built-in `--mask` token kinds, in the order they are applied.
+const MASKS: [(&str, &str, &str); 5] = [
+    (
+        "timestamp",
+        "<TIMESTAMP>",
+        r"\b\d{4}-\d{2}-\d{2}(?:[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?)?\b|\b\d{2}:\d{2}:\d{2}(?:[.,]\d+)?\b",
+    ),
+    ("uuid", "<UUID>", r"\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b"),
+    ("ip", "<IP>", r"\b(?:\d{1,3}\.){3}\d{1,3}\b"),
+    ("numbers", "<NUM>", r"\b\d+(?:\.\d+)?\b"),
+    ("hex", "<HEX>", r"\b0[xX][0-9a-fA-F]+\b|\b[0-9a-fA-F]{8,}\b"),
+];
+
+/// How `--similarity` measures how close two lines are.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    /// One minus the edit distance divided by the length of the longer line.
+    Edit,
+    /// Shared words divided by all distinct words of both lines.
+    Jaccard,
+}
+
+/// How the frequency report orders its lines.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortBy {
+    Count,
+    FirstSeen,
+    Lexical,
 }
 
+#[derive(Default)]
 struct PrevItem {
-    line: String,
-    count: u32,
+    line: Vec<u8>,
+    count: u64,
+    /// Line numbers of the first and the last line of the group.
+    first: u64,
+    last: u64,
+    /// Every line of the group, only kept for `-D`.
+    lines: Vec<Vec<u8>>,
+}
+
+/// Minimum width of the count column, which grows for larger counts.
+const COUNT_WIDTH: usize = 4;
+
+/// The comparison key of a line, folded for `-i`. Keys that are not valid
+/// UTF-8 are only folded in their ASCII bytes.
+#[derive(PartialEq, Eq, Hash)]
+enum Key {
+    Exact(Vec<u8>),
+    Folded(UniCase<String>),
+    FoldedBytes(Vec<u8>),
+}
+
+/// Removes duplicates anywhere in the input for `--global`.
+///
+/// New lines are printed right away as long as the keys seen so far fit in
+/// `max_memory` bytes. After that, the seen keys and all further lines are
+/// spread over hashed partitions in a temporary directory. At the end every
+/// partition is deduplicated on its own and the survivors are merged back
+/// in input order.
+struct GlobalDedup {
+    max_memory: usize,
+    memory: usize,
+    seen: HashSet<Key>,
+    partitions: Option<Partitions>,
+    seq: u64,
+}
+
+struct Partitions {
+    dir: tempfile::TempDir,
+    writers: Vec<BufWriter<File>>,
 }
 
 // ############################################################################
@@ -34,17 +200,26 @@ pub fn get_args() -> MyResult<Config> {
         .author("masawamor")
         .about("Rust uniqr")
         .arg(
-            Arg::with_name(ARG_IN_FILE)
-                .value_name("IN_FILE")
-                .help("Input file")
-                .multiple(false)
+            Arg::with_name(ARG_FILES)
+                .value_name("FILE")
+                .help("Input files read as one stream; without --output, exactly two are INPUT OUTPUT as in GNU uniq")
+                .multiple(true)
                 .default_value("-")
         )
         .arg(
-            Arg::with_name(ARG_OUT_FILE)
+            Arg::with_name(ARG_OUTPUT)
+                .short("o")
+                .long("output")
                 .value_name("OUT_FILE")
-                .help("Output file")
-                .multiple(false)
+                .help("Output file, - for standard output")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name(ARG_IN_PLACE)
+                .long("in-place")
+                .help("Rewrite each input file with its own result")
+                .takes_value(false)
+                .conflicts_with(ARG_OUTPUT)
         )
         .arg(
             Arg::with_name(ARG_COUNT)
@@ -55,60 +230,1177 @@ pub fn get_args() -> MyResult<Config> {
                 .multiple(false)
                 .takes_value(false)
         )
+        .arg(
+            Arg::with_name(ARG_REPEATED)
+                .short("d")
+                .long("repeated")
+                .help("Only print duplicate lines, one for each group")
+                .takes_value(false)
+        )
+        .arg(
+            Arg::with_name(ARG_UNIQUE)
+                .short("u")
+                .long("unique")
+                .help("Only print unique lines")
+                .takes_value(false)
+        )
+        .arg(
+            Arg::with_name(ARG_ALL_REPEATED)
+                .short("D")
+                .long("all-repeated")
+                .value_name("METHOD")
+                .help("Print all duplicate lines, delimiting groups with METHOD")
+                .possible_values(&["none", "prepend", "separate"])
+                .min_values(0)
+                .require_equals(true)
+        )
+        .arg(
+            Arg::with_name(ARG_IGNORE_CASE)
+                .short("i")
+                .long("ignore-case")
+                .help("Ignore differences in case when comparing")
+                .takes_value(false)
+        )
+        .arg(
+            Arg::with_name(ARG_SKIP_FIELDS)
+                .short("f")
+                .long("skip-fields")
+                .value_name("N")
+                .help("Avoid comparing the first N fields")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name(ARG_SKIP_CHARS)
+                .short("s")
+                .long("skip-chars")
+                .value_name("N")
+                .help("Avoid comparing the first N characters")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name(ARG_CHECK_CHARS)
+                .short("w")
+                .long("check-chars")
+                .value_name("N")
+                .help("Compare no more than N characters in lines")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name(ARG_GLOBAL)
+                .long("global")
+                .help("Remove duplicate lines anywhere in the input, keeping the first")
+                .takes_value(false)
+                .conflicts_with_all(&[ARG_COUNT, ARG_REPEATED, ARG_UNIQUE, ARG_ALL_REPEATED])
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_MEMORY)
+                .long("max-memory")
+                .value_name("SIZE")
+                .help("Memory for --global before it moves to temporary files (K, M, G suffixes)")
+                .default_value("256M")
+        )
+        .arg(
+            Arg::with_name(ARG_TOP)
+                .long("top")
+                .value_name("N")
+                .help("Report only the N most frequent lines of the whole input")
+                .takes_value(true)
+                .conflicts_with_all(&[ARG_ALL_REPEATED, ARG_GLOBAL])
+        )
+        .arg(
+            Arg::with_name(ARG_SORT)
+                .long("sort")
+                .value_name("ORDER")
+                .help("Report counts over the whole input, ordered by ORDER")
+                .possible_values(&["count", "first-seen", "lexical"])
+                .require_equals(true)
+                .conflicts_with_all(&[ARG_ALL_REPEATED, ARG_GLOBAL])
+        )
+        .arg(
+            Arg::with_name(ARG_PERCENT)
+                .long("percent")
+                .help("Show each line's share of the input in the report")
+                .takes_value(false)
+                .conflicts_with_all(&[ARG_ALL_REPEATED, ARG_GLOBAL])
+        )
+        .arg(
+            Arg::with_name(ARG_CUMULATIVE)
+                .long("cumulative")
+                .help("Show the running share of the input in the report")
+                .takes_value(false)
+                .conflicts_with_all(&[ARG_ALL_REPEATED, ARG_GLOBAL])
+        )
+        .arg(
+            Arg::with_name(ARG_MASK)
+                .long("mask")
+                .value_name("KINDS")
+                .help("Mask these variable tokens before comparing and print templates with an example line")
+                .possible_values(&MASKS.map(|(kind, _, _)| kind))
+                .use_delimiter(true)
+                .require_equals(true)
+        )
+        .arg(
+            Arg::with_name(ARG_MASK_REGEX)
+                .long("mask-regex")
+                .value_name("REGEX")
+                .help("Also mask the tokens matching REGEX")
+                .multiple(true)
+                .number_of_values(1)
+        )
+        .arg(
+            Arg::with_name(ARG_KEY_FIELD)
+                .long("key-field")
+                .value_name("N")
+                .help("Compare only field N of each line")
+                .takes_value(true)
+                .conflicts_with(ARG_KEY_REGEX)
+        )
+        .arg(
+            Arg::with_name(ARG_DELIMITER)
+                .short("t")
+                .long("delimiter")
+                .value_name("DELIM")
+                .help("Separate --key-field fields by DELIM instead of blanks")
+                .takes_value(true)
+                .requires(ARG_KEY_FIELD)
+        )
+        .arg(
+            Arg::with_name(ARG_CSV)
+                .long("csv")
+                .help("Read --key-field fields as CSV, with ',' as the default delimiter")
+                .takes_value(false)
+                .requires(ARG_KEY_FIELD)
+        )
+        .arg(
+            Arg::with_name(ARG_KEY_REGEX)
+                .long("key-regex")
+                .value_name("REGEX")
+                .help("Compare only the first capture group of REGEX")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name(ARG_KEEP)
+                .long("keep")
+                .value_name("WHICH")
+                .help("Print the first or the last line of each group")
+                .possible_values(&["first", "last"])
+                .require_equals(true)
+                .conflicts_with(ARG_GLOBAL)
+        )
+        .arg(
+            Arg::with_name(ARG_GROUP)
+                .long("group")
+                .value_name("METHOD")
+                .help("Print all lines, delimiting groups with METHOD")
+                .possible_values(&["separate", "prepend", "append", "both"])
+                .min_values(0)
+                .require_equals(true)
+                .conflicts_with_all(&[
+                    ARG_COUNT, ARG_REPEATED, ARG_UNIQUE, ARG_ALL_REPEATED, ARG_GLOBAL,
+                    ARG_TOP, ARG_SORT, ARG_PERCENT, ARG_CUMULATIVE,
+                ])
+        )
+        .arg(
+            Arg::with_name(ARG_FORMAT)
+                .long("format")
+                .value_name("FORMAT")
+                .help("Print counted records as FORMAT")
+                .possible_values(&["text", "json", "jsonl", "csv"])
+                .default_value("text")
+                .require_equals(true)
+        )
+        .arg(
+            Arg::with_name(ARG_ZERO_TERMINATED)
+                .short("z")
+                .long("zero-terminated")
+                .help("Line delimiter is NUL, not newline")
+                .takes_value(false)
+        )
+        .arg(
+            Arg::with_name(ARG_SIMILARITY)
+                .long("similarity")
+                .value_name("THRESHOLD")
+                .help("Treat adjacent lines at least THRESHOLD (0 to 1) similar as duplicates")
+                .takes_value(true)
+                .conflicts_with_all(&[ARG_GLOBAL, ARG_TOP, ARG_SORT, ARG_PERCENT, ARG_CUMULATIVE])
+        )
+        .arg(
+            Arg::with_name(ARG_METRIC)
+                .long("metric")
+                .value_name("METRIC")
+                .help("Measure --similarity by normalized edit distance or word Jaccard index")
+                .possible_values(&["edit", "jaccard"])
+                .default_value("edit")
+                .require_equals(true)
+        )
         .get_matches();
+    
+    let all_repeated = if matches.is_present(ARG_ALL_REPEATED) {
+        match matches.value_of(ARG_ALL_REPEATED) {
+            Some("prepend") => Some(Delimit::Prepend),
+            Some("separate") => Some(Delimit::Separate),
+            _ => Some(Delimit::None),
+        }
+    } else {
+        None
+    };
+
+    let global = matches.is_present(ARG_GLOBAL);
+
+    let group = if matches.is_present(ARG_GROUP) {
+        match matches.value_of(ARG_GROUP) {
+            Some("prepend") => Some(Delimit::Prepend),
+            Some("append") => Some(Delimit::Append),
+            Some("both") => Some(Delimit::Both),
+            _ => Some(Delimit::Separate),
+        }
+    } else {
+        None
+    };
+
+    let format = match matches.value_of(ARG_FORMAT) {
+        Some("json") => OutputFormat::Json,
+        Some("jsonl") => OutputFormat::Jsonl,
+        Some("csv") => OutputFormat::Csv,
+        _ => OutputFormat::Text,
+    };
+    if format != OutputFormat::Text && (global || all_repeated.is_some() || group.is_some()) {
+        return Err("--format cannot be used with --global, --all-repeated or --group".into());
+    }
+
+    let skip_fields = matches.value_of(ARG_SKIP_FIELDS)
+        .map(parse_count)
+        .transpose()
+        .map_err(|e| format!("invalid number of fields to skip: {}", e))?
+        .unwrap_or(0);
+    
+    let skip_chars = matches.value_of(ARG_SKIP_CHARS)
+        .map(parse_count)
+        .transpose()
+        .map_err(|e| format!("invalid number of characters to skip: {}", e))?
+        .unwrap_or(0);
+    
+    let check_chars = matches.value_of(ARG_CHECK_CHARS)
+        .map(parse_count)
+        .transpose()
+        .map_err(|e| format!("invalid number of characters to compare: {}", e))?;
+
+    let max_memory = matches.value_of(ARG_MAX_MEMORY)
+        .map(parse_size)
+        .transpose()
+        .map_err(|e| format!("invalid --max-memory: {}", e))?
+        .unwrap();
+
+    let top = matches.value_of(ARG_TOP)
+        .map(parse_count)
+        .transpose()
+        .map_err(|e| format!("invalid number of lines to report: {}", e))?;
+
+    let sort = matches.value_of(ARG_SORT).map(|order| match order {
+        "first-seen" => SortBy::FirstSeen,
+        "lexical" => SortBy::Lexical,
+        _ => SortBy::Count,
+    });
+
+    let kinds: Vec<&str> = matches.values_of(ARG_MASK).map(Iterator::collect).unwrap_or_default();
+    let mut masks: Vec<Mask> = MASKS.iter()
+        .filter(|(kind, _, _)| kinds.contains(kind))
+        .map(|(_, placeholder, pattern)| Mask { regex: Regex::new(pattern).unwrap(), placeholder })
+        .collect();
+    for pattern in matches.values_of(ARG_MASK_REGEX).into_iter().flatten() {
+        let regex = Regex::new(pattern).map_err(|e| format!("invalid --mask-regex: {}", e))?;
+        masks.push(Mask { regex, placeholder: "<*>" });
+    }
+
+    let key_field = match matches.value_of(ARG_KEY_FIELD).map(parse_count).transpose() {
+        Ok(Some(0)) => return Err("invalid key field: '0'".into()),
+        Ok(key_field) => key_field,
+        Err(e) => return Err(format!("invalid key field: {}", e).into()),
+    };
+
+    let delimiter = match matches.value_of(ARG_DELIMITER) {
+        Some(delimiter) => {
+            let mut chars = delimiter.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(c),
+                _ => return Err(format!("--delimiter \"{}\" must be a single character", delimiter).into()),
+            }
+        },
+        None => None,
+    };
+
+    let key_regex = matches.value_of(ARG_KEY_REGEX)
+        .map(Regex::new)
+        .transpose()
+        .map_err(|e| format!("invalid --key-regex: {}", e))?;
+
+    let similarity = match matches.value_of(ARG_SIMILARITY).map(str::parse::<f64>) {
+        Some(Ok(threshold)) if (0.0..=1.0).contains(&threshold) => Some(threshold),
+        Some(_) => {
+            let value = matches.value_of(ARG_SIMILARITY).unwrap();
+            return Err(format!("invalid similarity threshold: '{}'", value).into());
+        },
+        None => None,
+    };
+
+    let metric = match matches.value_of(ARG_METRIC) {
+        Some("jaccard") => Metric::Jaccard,
+        _ => Metric::Edit,
+    };
+
+    let in_place = matches.is_present(ARG_IN_PLACE);
+    let mut in_files: Vec<String> = matches.values_of_lossy(ARG_FILES).unwrap();
+    let mut out_file = matches.value_of(ARG_OUTPUT).map(String::from);
+    if out_file.is_none() && !in_place && in_files.len() == 2 {
+        // The GNU form INPUT OUTPUT, which must not clobber a file that may
+        // have been meant as a second input.
+        let output = in_files.pop().unwrap();
+        if std::fs::metadata(&output).is_ok_and(|metadata| metadata.is_file() && metadata.len() > 0) {
+            return Err(format!(
+                "{}: refusing to overwrite a non-empty OUTPUT; use -o {} to replace it, or -o - to read both files",
+                output, output,
+            ).into());
+        }
+        out_file = Some(output);
+    }
+    if in_place && in_files.iter().any(|filename| filename == "-") {
+        return Err("--in-place cannot rewrite standard input".into());
+    }
 
     Ok(Config {
-        in_file: matches.value_of_lossy(ARG_IN_FILE).unwrap().to_string(),
-        out_file: matches.value_of(ARG_OUT_FILE).map(String::from),
+        in_files,
+        out_file,
+        in_place,
         count: matches.is_present(ARG_COUNT),
+        repeated: matches.is_present(ARG_REPEATED) || all_repeated.is_some(),
+        unique: matches.is_present(ARG_UNIQUE),
+        all_repeated,
+        ignore_case: matches.is_present(ARG_IGNORE_CASE),
+        skip_fields,
+        skip_chars,
+        check_chars,
+        global,
+        max_memory,
+        top,
+        sort,
+        percent: matches.is_present(ARG_PERCENT),
+        cumulative: matches.is_present(ARG_CUMULATIVE),
+        masks,
+        key_field,
+        delimiter,
+        csv: matches.is_present(ARG_CSV),
+        key_regex,
+        keep_last: matches.value_of(ARG_KEEP) == Some("last"),
+        group,
+        format,
+        terminator: if matches.is_present(ARG_ZERO_TERMINATED) { b'\0' } else { b'\n' },
+        similarity,
+        metric,
     })
 }
 
+fn parse_count(val: &str) -> MyResult<usize> {
+    val.parse().map_err(|_| format!("'{}'", val).into())
+}
+
+/// Parses a number of bytes with an optional K, M or G suffix.
+fn parse_size(val: &str) -> MyResult<usize> {
+    let (digits, unit) = match val.char_indices().last() {
+        Some((pos, 'K' | 'k')) => (&val[..pos], 1 << 10),
+        Some((pos, 'M' | 'm')) => (&val[..pos], 1 << 20),
+        Some((pos, 'G' | 'g')) => (&val[..pos], 1 << 30),
+        _ => (val, 1),
+    };
+    digits.parse::<usize>().ok()
+        .and_then(|n| n.checked_mul(unit))
+        .ok_or_else(|| format!("'{}'", val).into())
+}
+
 pub fn run(config: Config) -> MyResult<()> {
     
-    let mut file = open(&config.in_file)
-        .map_err(|e| format!("{}: {}", config.in_file, e) )?;
+    if config.in_place {
+        for filename in &config.in_files {
+            rewrite(&config, filename).map_err(|e| format!("{}: {}", filename, e))?;
+        }
+        return Ok(());
+    }
     
-    let mut out_file: Box<dyn Write> = match &config.out_file {
-        Some(filename) => Box::new(std::fs::File::create(filename)?),
-        None => Box::new(io::stdout()),
+    let mut file: Box<dyn BufRead> = Box::new(io::empty());
+    for (index, filename) in config.in_files.iter().enumerate() {
+        let next = open(filename).map_err(|e| format!("{}: {}", filename, e))?;
+        file = if index + 1 < config.in_files.len() {
+            Box::new(file.chain(BufReader::new(Terminated::new(next, config.terminator))))
+        } else {
+            Box::new(file.chain(next))
+        };
+    }
+    
+    let out_file: Box<dyn Write> = match config.out_file.as_deref() {
+        None | Some("-") => Box::new(BufWriter::new(io::stdout())),
+        Some(filename) => {
+            check_not_input(&config, filename)?;
+            Box::new(BufWriter::new(File::create(filename)?))
+        },
     };
     
-    let mut prev_item = PrevItem { line: "".to_string(), count: 0 };
+    uniq(&config, file, out_file)
+}
+
+/// Ends the last line of an input that is followed by another one, so that
+/// it does not run into the first line of the next input.
+struct Terminated<R> {
+    inner: R,
+    terminator: u8,
+    last: Option<u8>,
+    finished: bool,
+}
+
+impl<R: Read> Terminated<R> {
+    fn new(inner: R, terminator: u8) -> Self {
+        Terminated { inner, terminator, last: None, finished: false }
+    }
+}
+
+impl<R: Read> Read for Terminated<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.finished || buf.is_empty() {
+            return Ok(0);
+        }
+        let bytes = self.inner.read(buf)?;
+        if bytes > 0 {
+            self.last = Some(buf[bytes - 1]);
+            return Ok(bytes);
+        }
+        self.finished = true;
+        match self.last {
+            Some(last) if last != self.terminator => {
+                buf[0] = self.terminator;
+                Ok(1)
+            },
+            _ => Ok(0),
+        }
+    }
+}
+
+/// Refuses an output file that is one of the inputs, before creating it
+/// truncates the input.
+fn check_not_input(config: &Config, out_file: &str) -> MyResult<()> {
+    let Ok(out) = same_file::Handle::from_path(out_file) else {
+        return Ok(());
+    };
+    for filename in &config.in_files {
+        let input = match filename.as_str() {
+            "-" => same_file::Handle::stdin(),
+            _ => same_file::Handle::from_path(filename),
+        };
+        if input.is_ok_and(|input| input == out) {
+            return Err(format!("{}: output file is the same as input file {}", out_file, filename).into());
+        }
+    }
+    Ok(())
+}
+
+/// Rewrites a file with its own result for `--in-place`, through a
+/// temporary file in the same directory that is renamed over it.
+fn rewrite(config: &Config, filename: &str) -> MyResult<()> {
+    let path = std::path::Path::new(filename);
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => std::path::Path::new("."),
+    };
+    
+    let file = open(filename)?;
+    let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+    uniq(config, file, BufWriter::new(temp.as_file_mut()))?;
+    temp.as_file().set_permissions(std::fs::metadata(path)?.permissions())?;
+    temp.persist(path)?;
+    
+    Ok(())
+}
+
+/// Writes the result of one input stream.
+fn uniq(config: &Config, mut file: impl BufRead, mut out_file: impl Write) -> MyResult<()> {
+    
+    if config.global {
+        let mut dedup = GlobalDedup::new(config.max_memory);
+        let mut line = Vec::new();
+        while file.read_until(config.terminator, &mut line)? > 0 {
+            dedup.push(config, &line, &mut out_file)?;
+            line.clear();
+        }
+        dedup.finish(config, &mut out_file)?;
+        out_file.flush()?;
+        return Ok(());
+    }
+    
+    if config.top.is_some() || config.sort.is_some() || config.percent || config.cumulative {
+        report(config, file, &mut out_file)?;
+        out_file.flush()?;
+        return Ok(());
+    }
+    
+    let mut prev_item = PrevItem::default();
+    let mut line_number = 0;
+    
+    show_header(&mut out_file, config)?;
+    
+    let mut line = Vec::new();
     
-    let mut line = String::new();
+    let mut num_groups_printed = 0;
+    
+    // The output is streamed, so the count column widens when a count needs
+    // more room; rows that were already written keep their width.
+    let mut width = COUNT_WIDTH;
     
     let mut print = |prev_item: &PrevItem| -> MyResult<()> {
-        if prev_item.count > 0 {
-            if config.count {
-                write!(out_file, "{:>4} {}", prev_item.count, prev_item.line)?;
-            } else {
-                write!(out_file, "{}", prev_item.line)?;
-            }
+        if prev_item.count == 0 {
+            return Ok(());
         }
+        
+        let is_repeated = prev_item.count > 1;
+        if (config.repeated && !is_repeated) || (config.unique && is_repeated) {
+            return Ok(());
+        }
+        
+        width = width.max(prev_item.count.to_string().len());
+        write_group(&mut out_file, config, width, prev_item, num_groups_printed)?;
+        num_groups_printed += 1;
+        
         Ok(())
     };
     
     loop {
         
-        let bytes = file.read_line(&mut line)?;
+        let bytes = file.read_until(config.terminator, &mut line)?;
         if bytes == 0 {
             break;
         }
+        line_number += 1;
         
-        if prev_item.line.trim_end() != line.trim_end() {
-            print(&prev_item);
+        if prev_item.count == 0 || !is_same(config, &prev_item.line, &line) {
+            print(&prev_item)?;
             
             prev_item.line = line.clone();
             prev_item.count = 1;
+            prev_item.first = line_number;
+            prev_item.lines.clear();
         } else {
             prev_item.count += 1;
+            if config.keep_last {
+                prev_item.line = line.clone();
+            }
+        }
+        prev_item.last = line_number;
+        if config.all_repeated.is_some() || config.group.is_some() {
+            prev_item.lines.push(line.clone());
         }
         line.clear();
     }
     
-    print(&prev_item);
+    print(&prev_item)?;
+    
+    if config.group == Some(Delimit::Both) && num_groups_printed > 0 {
+        out_file.write_all(&[config.terminator])?;
+    }
+    show_footer(&mut out_file, config)?;
     
+    out_file.flush()?;
+    Ok(())
+}
+
+/// Writes one group of the streaming output; `index` is the number of groups
+/// written before it.
+fn write_group(out_file: &mut impl Write, config: &Config, width: usize, item: &PrevItem, index: usize) -> MyResult<()> {
+    match config.all_repeated.or(config.group) {
+        None if config.format != OutputFormat::Text => {
+            show_record(out_file, config, item, (0.0, 0.0), index)?;
+        },
+        None if !config.masks.is_empty() => {
+            write!(out_file, "{:>width$} ", item.count)?;
+            write_template(out_file, config, &item.line)?;
+        },
+        None => write_line(out_file, config, width, item.count, &item.line)?,
+        Some(delimit) => {
+            let before = match delimit {
+                Delimit::Prepend | Delimit::Both => true,
+                Delimit::Separate => index > 0,
+                Delimit::None | Delimit::Append => false,
+            };
+            if before {
+                out_file.write_all(&[config.terminator])?;
+            }
+            for line in &item.lines {
+                write_line(out_file, config, width, item.count, line)?;
+            }
+            if delimit == Delimit::Append {
+                out_file.write_all(&[config.terminator])?;
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Whether two lines belong to the same group, after skipping fields and
+/// characters the same way GNU uniq does.
+fn is_same(config: &Config, a: &[u8], b: &[u8]) -> bool {
+    let (a, b) = (mask(config, a), mask(config, b));
+    let (a, b) = (compare_key(config, &a), compare_key(config, &b));
+    if let Some(threshold) = config.similarity {
+        return similarity(config, &a, &b) >= threshold;
+    }
+    if config.ignore_case {
+        match (std::str::from_utf8(&a), std::str::from_utf8(&b)) {
+            (Ok(a), Ok(b)) => unicase::eq(a, b),
+            _ => a.eq_ignore_ascii_case(&b),
+        }
+    } else {
+        a == b
+    }
+}
+
+/// Returns how similar two keys are by `--metric`, from 0 for nothing in
+/// common to 1 for equal keys.
+fn similarity(config: &Config, a: &[u8], b: &[u8]) -> f64 {
+    // Keys that `-i` alone would group are equal, so that a threshold of 1
+    // behaves like an exact comparison.
+    if seen_key(config, a) == seen_key(config, b) {
+        return 1.0;
+    }
+    // Upper casing first expands characters such as ß the way case folding
+    // does.
+    let fold = |key: &[u8]| {
+        let key = String::from_utf8_lossy(key);
+        if config.ignore_case { key.to_uppercase().to_lowercase() } else { key.into_owned() }
+    };
+    let (a, b) = (fold(a), fold(b));
+    
+    match config.metric {
+        Metric::Edit => {
+            let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+            let len = a.len().max(b.len());
+            if len == 0 {
+                return 1.0;
+            }
+            1.0 - edit_distance(&a, &b) as f64 / len as f64
+        },
+        Metric::Jaccard => {
+            let a: HashSet<&str> = a.split_whitespace().collect();
+            let b: HashSet<&str> = b.split_whitespace().collect();
+            let union = a.union(&b).count();
+            if union == 0 {
+                return 1.0;
+            }
+            a.intersection(&b).count() as f64 / union as f64
+        },
+    }
+}
+
+/// Returns the Levenshtein distance between two sequences.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != cb);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+/// Replaces the tokens matched by `--mask` and `--mask-regex` with their
+/// placeholders.
+fn mask<'a>(config: &Config, line: &'a [u8]) -> Cow<'a, [u8]> {
+    let mut line = Cow::Borrowed(line);
+    for mask in &config.masks {
+        if let Cow::Owned(masked) = mask.regex.replace_all(&line, mask.placeholder.as_bytes()) {
+            line = Cow::Owned(masked);
+        }
+    }
+    line
+}
+
+/// Writes the masked template of a line followed by the line itself as an
+/// example, separated by a tab.
+fn write_template(out_file: &mut impl Write, config: &Config, line: &[u8]) -> MyResult<()> {
+    let template = mask(config, line);
+    out_file.write_all(strip_terminator(config, &template))?;
+    out_file.write_all(b"\t")?;
+    out_file.write_all(line.strip_suffix(&[config.terminator]).unwrap_or(line))?;
+    out_file.write_all(&[config.terminator])?;
+    Ok(())
+}
+
+/// Strips the line terminator, and a carriage return before a newline.
+fn strip_terminator<'a>(config: &Config, line: &'a [u8]) -> &'a [u8] {
+    let line = line.strip_suffix(&[config.terminator]).unwrap_or(line);
+    match config.terminator {
+        b'\n' => line.strip_suffix(b"\r").unwrap_or(line),
+        _ => line,
+    }
+}
+
+/// Returns the part of the line that is compared: the `--key-field` or
+/// `--key-regex` key if any, from which `-f` fields and then `-s` characters
+/// are skipped, and at most `-w` characters are kept. Trailing blanks are
+/// ignored unless records are NUL-terminated.
+fn compare_key<'a>(config: &Config, line: &'a [u8]) -> Cow<'a, [u8]> {
+    let line = match config.terminator {
+        b'\n' => line.trim_ascii_end(),
+        _ => line.strip_suffix(&[config.terminator]).unwrap_or(line),
+    };
+    match select_key(config, line) {
+        Cow::Borrowed(key) => Cow::Borrowed(skip_key(config, key)),
+        Cow::Owned(key) => Cow::Owned(skip_key(config, &key).to_vec()),
+    }
+}
+
+/// Selects field `--key-field` or the first capture group of `--key-regex`.
+/// Lines that `--key-regex` does not match are compared as a whole.
+fn select_key<'a>(config: &Config, line: &'a [u8]) -> Cow<'a, [u8]> {
+    if let Some(regex) = &config.key_regex {
+        return match regex.captures(line) {
+            Some(caps) => Cow::Borrowed(caps.get(1).or(caps.get(0)).unwrap().as_bytes()),
+            None => Cow::Borrowed(line),
+        };
+    }
+    
+    let Some(key_field) = config.key_field else {
+        return Cow::Borrowed(line);
+    };
+    let index = key_field - 1;
+    
+    let mut buf = [0; 4];
+    match config.delimiter {
+        _ if config.csv => {
+            let delimiter = config.delimiter.unwrap_or(',').encode_utf8(&mut buf);
+            Cow::Owned(csv_field(line, delimiter.as_bytes(), index))
+        },
+        Some(delimiter) => Cow::Borrowed(nth_field(line, delimiter.encode_utf8(&mut buf).as_bytes(), index)),
+        None => Cow::Borrowed(
+            line.split(|&b| b == b' ' || b == b'\t').filter(|field| !field.is_empty()).nth(index).unwrap_or(b"")
+        ),
+    }
+}
+
+/// Returns field `index` of a line whose fields are separated by `delimiter`.
+fn nth_field<'a>(line: &'a [u8], delimiter: &[u8], index: usize) -> &'a [u8] {
+    let find = |bytes: &[u8]| bytes.windows(delimiter.len()).position(|window| window == delimiter);
+    
+    let mut rest = line;
+    for _ in 0..index {
+        match find(rest) {
+            Some(pos) => rest = &rest[pos + delimiter.len()..],
+            None => return b"",
+        }
+    }
+    match find(rest) {
+        Some(pos) => &rest[..pos],
+        None => rest,
+    }
+}
+
+/// Returns field `index` of a CSV line, without its quotes. Quoted fields
+/// cannot span lines.
+fn csv_field(line: &[u8], delimiter: &[u8], index: usize) -> Vec<u8> {
+    let mut field = vec![];
+    let mut num_fields = 0;
+    let mut quoted = false;
+    
+    let mut pos = 0;
+    while pos < line.len() {
+        let b = line[pos];
+        if quoted {
+            if b != b'"' {
+                field.push(b);
+            } else if line.get(pos + 1) == Some(&b'"') {
+                field.push(b'"');
+                pos += 1;
+            } else {
+                quoted = false;
+            }
+        } else if b == b'"' {
+            quoted = true;
+        } else if line[pos..].starts_with(delimiter) {
+            if num_fields == index {
+                return field;
+            }
+            num_fields += 1;
+            field.clear();
+            pos += delimiter.len();
+            continue;
+        } else {
+            field.push(b);
+        }
+        pos += 1;
+    }
+    
+    if num_fields == index { field } else { vec![] }
+}
+
+/// Skips `-f` fields and then `-s` characters, and keeps at most `-w`
+/// characters. A field is a run of blanks followed by non-blank characters.
+fn skip_key<'a>(config: &Config, key: &'a [u8]) -> &'a [u8] {
+    let mut key = key;
+    
+    let is_blank = |b: &u8| *b == b' ' || *b == b'\t';
+    for _ in 0..config.skip_fields {
+        key = &key[key.iter().position(|b| !is_blank(b)).unwrap_or(key.len())..];
+        key = &key[key.iter().position(is_blank).unwrap_or(key.len())..];
+    }
+    
+    key = match char_offset(key, config.skip_chars) {
+        Some(pos) => &key[pos..],
+        None => b"",
+    };
+    
+    if let Some(check_chars) = config.check_chars
+        && let Some(pos) = char_offset(key, check_chars) {
+        key = &key[..pos];
+    }
+    
+    key
+}
+
+/// Returns the byte offset of character `n`, counting every byte that is
+/// not valid UTF-8 as a character of its own.
+fn char_offset(bytes: &[u8], mut n: usize) -> Option<usize> {
+    let mut offset = 0;
+    for chunk in bytes.utf8_chunks() {
+        for (pos, _) in chunk.valid().char_indices() {
+            if n == 0 {
+                return Some(offset + pos);
+            }
+            n -= 1;
+        }
+        offset += chunk.valid().len();
+        
+        if n < chunk.invalid().len() {
+            return Some(offset + n);
+        }
+        n -= chunk.invalid().len();
+        offset += chunk.invalid().len();
+    }
+    None
+}
+
+/// Counts every line over the whole input and prints the counts ordered by
+/// `--sort`, keeping the first `--top` of them.
+fn report(config: &Config, mut file: impl BufRead, out_file: &mut impl Write) -> MyResult<()> {
+    let mut index: HashMap<Key, usize> = HashMap::new();
+    let mut items: Vec<PrevItem> = vec![];
+    let mut total = 0;
+    
+    let mut line = Vec::new();
+    while file.read_until(config.terminator, &mut line)? > 0 {
+        total += 1;
+        let masked = mask(config, &line);
+        let key = seen_key(config, &compare_key(config, &masked));
+        match index.get(&key) {
+            Some(&i) => {
+                items[i].count += 1;
+                items[i].last = total;
+                if config.keep_last {
+                    items[i].line = line.clone();
+                }
+            },
+            None => {
+                index.insert(key, items.len());
+                items.push(PrevItem { line: line.clone(), count: 1, first: total, last: total, lines: vec![] });
+            },
+        }
+        line.clear();
+    }
+    drop(index);
+    
+    items.retain(|item| {
+        let is_repeated = item.count > 1;
+        (!config.repeated || is_repeated) && (!config.unique || !is_repeated)
+    });
+    
+    match config.sort.unwrap_or(SortBy::Count) {
+        SortBy::Count => items.sort_by_key(|item| Reverse(item.count)),
+        SortBy::FirstSeen => {},
+        SortBy::Lexical => items.sort_by_cached_key(|item| mask(config, &item.line).into_owned()),
+    }
+    items.truncate(config.top.unwrap_or(usize::MAX));
+    
+    let width = items.iter()
+        .map(|item| item.count.to_string().len())
+        .fold(COUNT_WIDTH, usize::max);
+    
+    let share = |count: u64| 100.0 * count as f64 / total as f64;
+    let mut cumulative = 0;
+    show_header(out_file, config)?;
+    for (index, item) in items.iter().enumerate() {
+        cumulative += item.count;
+        if config.format != OutputFormat::Text {
+            show_record(out_file, config, item, (share(item.count), share(cumulative)), index)?;
+            continue;
+        }
+        write!(out_file, "{:>width$} ", item.count)?;
+        if config.percent {
+            write!(out_file, "{:>6.2}% ", share(item.count))?;
+        }
+        if config.cumulative {
+            write!(out_file, "{:>6.2}% ", share(cumulative))?;
+        }
+        if config.masks.is_empty() {
+            out_file.write_all(&item.line)?;
+            // Sorting can move an unterminated last line before others.
+            if item.line.last() != Some(&config.terminator) {
+                out_file.write_all(&[config.terminator])?;
+            }
+        } else {
+            write_template(out_file, config, &item.line)?;
+        }
+    }
+    show_footer(out_file, config)?;
+    
+    Ok(())
+}
+
+/// The fields of a structured record. `shares` are the `--percent` and
+/// `--cumulative` shares of the report.
+fn record_fields(config: &Config, item: &PrevItem, shares: (f64, f64)) -> Vec<(&'static str, serde_json::Value)> {
+    let line = strip_terminator(config, &item.line);
+    let mut fields = vec![("count", item.count.into()), ("line", String::from_utf8_lossy(line).into())];
+    if !config.masks.is_empty() {
+        let template = String::from_utf8_lossy(&mask(config, line)).into_owned();
+        fields.push(("template", template.into()));
+    }
+    fields.push(("first_line", item.first.into()));
+    fields.push(("last_line", item.last.into()));
+    
+    let round = |share: f64| (share * 100.0).round() / 100.0;
+    if config.percent {
+        fields.push(("percent", round(shares.0).into()));
+    }
+    if config.cumulative {
+        fields.push(("cumulative", round(shares.1).into()));
+    }
+    fields
+}
+
+fn show_header(out_file: &mut impl Write, config: &Config) -> MyResult<()> {
+    match config.format {
+        OutputFormat::Json => write!(out_file, "[")?,
+        OutputFormat::Csv => {
+            let header: Vec<&str> = record_fields(config, &PrevItem::default(), (0.0, 0.0))
+                .iter().map(|(name, _)| *name).collect();
+            csv::Writer::from_writer(out_file).write_record(header)?;
+        },
+        OutputFormat::Text | OutputFormat::Jsonl => {},
+    }
+    Ok(())
+}
+
+/// Prints one group as a record. `index` is the number of records already
+/// printed.
+fn show_record(
+    out_file: &mut impl Write,
+    config: &Config,
+    item: &PrevItem,
+    shares: (f64, f64),
+    index: usize,
+) -> MyResult<()> {
+    let fields = record_fields(config, item, shares);
+    
+    match config.format {
+        OutputFormat::Json | OutputFormat::Jsonl => {
+            let record: serde_json::Map<String, serde_json::Value> = fields.into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect();
+            let record = serde_json::Value::Object(record);
+            
+            if config.format == OutputFormat::Json {
+                let separator = if index == 0 { "" } else { "," };
+                write!(out_file, "{}\n  {}", separator, record)?;
+            } else {
+                writeln!(out_file, "{}", record)?;
+            }
+        },
+        OutputFormat::Csv => {
+            let record: Vec<String> = fields.into_iter()
+                .map(|(_, value)| match value {
+                    serde_json::Value::String(value) => value,
+                    value => value.to_string(),
+                })
+                .collect();
+            csv::Writer::from_writer(out_file).write_record(record)?;
+        },
+        OutputFormat::Text => {},
+    }
+    Ok(())
+}
+
+fn show_footer(out_file: &mut impl Write, config: &Config) -> MyResult<()> {
+    if config.format == OutputFormat::Json {
+        writeln!(out_file, "\n]")?;
+    }
+    Ok(())
+}
+
+fn seen_key(config: &Config, key: &[u8]) -> Key {
+    if !config.ignore_case {
+        return Key::Exact(key.to_vec());
+    }
+    match std::str::from_utf8(key) {
+        Ok(key) => Key::Folded(UniCase::new(key.to_string())),
+        Err(_) => Key::FoldedBytes(key.to_ascii_lowercase()),
+    }
+}
+
+impl GlobalDedup {
+    fn new(max_memory: usize) -> Self {
+        GlobalDedup { max_memory, memory: 0, seen: HashSet::new(), partitions: None, seq: 0 }
+    }
+    
+    fn push(&mut self, config: &Config, line: &[u8], out_file: &mut impl Write) -> MyResult<()> {
+        let masked = mask(config, line);
+        let key = &compare_key(config, &masked);
+        self.seq += 1;
+        
+        if let Some(partitions) = &mut self.partitions {
+            return partitions.write(config, self.seq, key, line);
+        }
+        
+        if self.seen.insert(seen_key(config, key)) {
+            out_file.write_all(line)?;
+            self.memory += key.len() + std::mem::size_of::<Key>() * 2;
+            if self.memory > self.max_memory {
+                self.spill(config)?;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Moves the seen keys to the partitions, from where they are only read
+    /// back one partition at a time.
+    fn spill(&mut self, config: &Config) -> MyResult<()> {
+        let mut partitions = Partitions::new()?;
+        for key in self.seen.drain() {
+            let key = match key {
+                Key::Exact(key) | Key::FoldedBytes(key) => key,
+                Key::Folded(key) => key.into_inner().into_bytes(),
+            };
+            partitions.write(config, SEEN_MARKER, &key, b"")?;
+        }
+        self.seen.shrink_to_fit();
+        self.partitions = Some(partitions);
+        Ok(())
+    }
+    
+    fn finish(self, config: &Config, out_file: &mut impl Write) -> MyResult<()> {
+        match self.partitions {
+            None => Ok(()),
+            Some(partitions) => partitions.finish(config, out_file),
+        }
+    }
+}
+
+impl Partitions {
+    fn new() -> MyResult<Self> {
+        let dir = tempfile::tempdir()?;
+        let writers = (0..NUM_PARTITIONS)
+            .map(|index| Ok(BufWriter::new(File::create(dir.path().join(format!("{}.in", index)))?)))
+            .collect::<MyResult<_>>()?;
+        Ok(Partitions { dir, writers })
+    }
+    
+    fn write(&mut self, config: &Config, seq: u64, key: &[u8], line: &[u8]) -> MyResult<()> {
+        let mut hasher = DefaultHasher::new();
+        seen_key(config, key).hash(&mut hasher);
+        let index = (hasher.finish() % NUM_PARTITIONS as u64) as usize;
+        write_record(&mut self.writers[index], seq, &[key, line])
+    }
+    
+    /// Deduplicates each partition and merges the surviving lines by their
+    /// sequence numbers.
+    fn finish(self, config: &Config, out_file: &mut impl Write) -> MyResult<()> {
+        let mut readers = vec![];
+        
+        for (index, writer) in self.writers.into_iter().enumerate() {
+            writer.into_inner().map_err(|e| e.into_error())?;
+            
+            let in_path = self.dir.path().join(format!("{}.in", index));
+            let out_path = self.dir.path().join(format!("{}.out", index));
+            let mut reader = BufReader::new(File::open(&in_path)?);
+            let mut writer = BufWriter::new(File::create(&out_path)?);
+            
+            let mut seen = HashSet::new();
+            while let Some((seq, fields)) = read_record(&mut reader, 2)? {
+                if seen.insert(seen_key(config, &fields[0])) && seq != SEEN_MARKER {
+                    write_record(&mut writer, seq, &[&fields[1]])?;
+                }
+            }
+            writer.flush()?;
+            std::fs::remove_file(&in_path)?;
+            
+            readers.push(BufReader::new(File::open(&out_path)?));
+        }
+        
+        let mut heads = vec![];
+        let mut heap = BinaryHeap::new();
+        for (index, reader) in readers.iter_mut().enumerate() {
+            let head = read_record(reader, 1)?;
+            if let Some((seq, _)) = &head {
+                heap.push(Reverse((*seq, index)));
+            }
+            heads.push(head);
+        }
+        
+        while let Some(Reverse((_, index))) = heap.pop() {
+            if let Some((_, fields)) = heads[index].take() {
+                out_file.write_all(&fields[0])?;
+            }
+            heads[index] = read_record(&mut readers[index], 1)?;
+            if let Some((seq, _)) = &heads[index] {
+                heap.push(Reverse((*seq, index)));
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+/// Writes a sequence number followed by length-prefixed fields.
+fn write_record(writer: &mut impl Write, seq: u64, fields: &[&[u8]]) -> MyResult<()> {
+    writer.write_all(&seq.to_le_bytes())?;
+    for field in fields {
+        writer.write_all(&(field.len() as u64).to_le_bytes())?;
+        writer.write_all(field)?;
+    }
+    Ok(())
+}
+
+/// Reads a record written by `write_record`, or `None` at the end of the file.
+fn read_record(reader: &mut impl Read, num_fields: usize) -> MyResult<Option<(u64, Vec<Vec<u8>>)>> {
+    let mut buf = [0; 8];
+    match reader.read_exact(&mut buf) {
+        Ok(()) => {},
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let seq = u64::from_le_bytes(buf);
+    
+    let mut fields = vec![];
+    for _ in 0..num_fields {
+        reader.read_exact(&mut buf)?;
+        let mut field = vec![0; u64::from_le_bytes(buf) as usize];
+        reader.read_exact(&mut field)?;
+        fields.push(field);
+    }
+    Ok(Some((seq, fields)))
+}
+
+fn write_line(out_file: &mut impl Write, config: &Config, width: usize, count: u64, line: &[u8]) -> MyResult<()> {
+    if config.count {
+        write!(out_file, "{:>width$} ", count)?;
+    }
+    out_file.write_all(line)?;
     Ok(())
 }
 
@@ -121,5 +1413,304 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
 
 // ############################################################################
 
-
-
+#[cfg(test)]
+mod tests {
+    use super::{
+        Config, GlobalDedup, MASKS, Mask, Metric, OutputFormat, SortBy,
+        char_offset, compare_key, csv_field, edit_distance, is_same, mask, parse_size, report, similarity, uniq,
+    };
+    use regex::bytes::Regex;
+    
+    fn config() -> Config {
+        Config {
+            in_files: vec!["-".to_string()],
+            out_file: None,
+            in_place: false,
+            count: false,
+            repeated: false,
+            unique: false,
+            all_repeated: None,
+            ignore_case: false,
+            skip_fields: 0,
+            skip_chars: 0,
+            check_chars: None,
+            global: false,
+            max_memory: 256 << 20,
+            top: None,
+            sort: None,
+            percent: false,
+            cumulative: false,
+            masks: vec![],
+            key_field: None,
+            delimiter: None,
+            csv: false,
+            key_regex: None,
+            keep_last: false,
+            group: None,
+            format: OutputFormat::Text,
+            terminator: b'\n',
+            similarity: None,
+            metric: Metric::Edit,
+        }
+    }
+    
+    fn key(config: &Config, line: &str) -> String {
+        String::from_utf8(compare_key(config, line.as_bytes()).into_owned()).unwrap()
+    }
+    
+    fn dedup(config: &Config, max_memory: usize, input: &[String]) -> String {
+        let mut out = vec![];
+        let mut dedup = GlobalDedup::new(max_memory);
+        for line in input {
+            dedup.push(config, line.as_bytes(), &mut out).unwrap();
+        }
+        dedup.finish(config, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+    
+    #[test]
+    fn test_compare_key() {
+        let line = "2024-01-01 12:00:00  INFO  started\n";
+        assert_eq!(key(&config(), line), "2024-01-01 12:00:00  INFO  started");
+        
+        let config = Config { skip_fields: 2, ..config() };
+        assert_eq!(key(&config, line), "  INFO  started");
+        
+        let config = Config { skip_fields: 2, skip_chars: 2, ..config };
+        assert_eq!(key(&config, line), "INFO  started");
+        
+        let config = Config { check_chars: Some(4), ..config };
+        assert_eq!(key(&config, line), "INFO");
+        
+        let config = Config { skip_fields: 10, ..config };
+        assert_eq!(key(&config, line), "");
+        
+        let config = Config { skip_chars: 3, check_chars: Some(2), ..super::tests::config() };
+        assert_eq!(key(&config, "日本語の文章"), "の文");
+    }
+    
+    #[test]
+    fn test_is_same_ignore_case() {
+        assert!(!is_same(&config(), "Straße\n".as_bytes(), "STRASSE\n".as_bytes()));
+        
+        let config = Config { ignore_case: true, ..config() };
+        assert!(is_same(&config, "Straße\n".as_bytes(), "STRASSE\n".as_bytes()));
+        assert!(is_same(&config, "ΣΊΣΥΦΟΣ".as_bytes(), "σίσυφος".as_bytes()));
+        assert!(!is_same(&config, "abc".as_bytes(), "abd".as_bytes()));
+    }
+    
+    #[test]
+    fn test_global_dedup() {
+        let input: Vec<String> = ["b\n", "a\n", "b\n", "c\n", "A\n", "a\n", "d\n", "c\n"]
+            .iter().map(|line| line.to_string()).collect();
+        
+        let global_config = Config { global: true, ..config() };
+        assert_eq!(dedup(&global_config, usize::MAX, &input), "b\na\nc\nA\nd\n");
+        
+        let config = Config { ignore_case: true, ..global_config };
+        assert_eq!(dedup(&config, usize::MAX, &input), "b\na\nc\nd\n");
+    }
+    
+    #[test]
+    fn test_global_dedup_spilled() {
+        let input: Vec<String> = (0..5000).map(|n| format!("line {}\n", (n * 7919) % 1237)).collect();
+        let global_config = Config { global: true, ..config() };
+        let expected = dedup(&global_config, usize::MAX, &input);
+        assert_eq!(expected.lines().count(), 1237);
+        
+        for max_memory in [0, 100, 10_000] {
+            assert_eq!(dedup(&global_config, max_memory, &input), expected);
+        }
+        
+        let config = Config { ignore_case: true, skip_chars: 2, ..global_config };
+        let expected = dedup(&config, usize::MAX, &input);
+        assert_eq!(dedup(&config, 0, &input), expected);
+    }
+    
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("100").unwrap(), 100);
+        assert_eq!(parse_size("4K").unwrap(), 4096);
+        assert_eq!(parse_size("256M").unwrap(), 256 << 20);
+        assert_eq!(parse_size("1g").unwrap(), 1 << 30);
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("1T").is_err());
+    }
+    
+    #[test]
+    fn test_report() {
+        let report = |config: &Config| {
+            let mut out = vec![];
+            report(config, "b\na\nc\na\nb\na\nd\n".as_bytes(), &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        
+        let report_config = config();
+        assert_eq!(report(&report_config), "   3 a\n   2 b\n   1 c\n   1 d\n");
+        
+        let report_config = Config { sort: Some(SortBy::FirstSeen), ..config() };
+        assert_eq!(report(&report_config), "   2 b\n   3 a\n   1 c\n   1 d\n");
+        
+        let report_config = Config { sort: Some(SortBy::Lexical), top: Some(2), ..config() };
+        assert_eq!(report(&report_config), "   3 a\n   2 b\n");
+        
+        let report_config = Config { top: Some(2), percent: true, cumulative: true, ..config() };
+        assert_eq!(report(&report_config), "   3  42.86%  42.86% a\n   2  28.57%  71.43% b\n");
+        
+        let report_config = Config { unique: true, ..config() };
+        assert_eq!(report(&report_config), "   1 c\n   1 d\n");
+        
+        let mut out = vec![];
+        let report_config = Config { sort: Some(SortBy::Lexical), ..config() };
+        super::report(&report_config, "c\nc\nb".as_bytes(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "   1 b\n   2 c\n");
+    }
+    
+    #[test]
+    fn test_mask() {
+        let masks = MASKS.iter()
+            .map(|(_, placeholder, pattern)| Mask { regex: Regex::new(pattern).unwrap(), placeholder })
+            .collect();
+        let mask_config = Config { masks, ..config() };
+        
+        assert_eq!(
+            mask(&mask_config, "2024-05-01T10:00:01.123Z took 35 ms from 10.0.0.12\n".as_bytes()),
+            "<TIMESTAMP> took <NUM> ms from <IP>\n".as_bytes(),
+        );
+        assert_eq!(
+            mask(&mask_config, "10:00:01 job 3f2b8c1e-0d4a-4c6e-9b1f-2a7d5e8c9f01 at 0x7ffd1234 id deadbeef42".as_bytes()),
+            "<TIMESTAMP> job <UUID> at <HEX> id <HEX>".as_bytes(),
+        );
+        assert_eq!(mask(&mask_config, "user a1 and version v2 accepted".as_bytes()), "user a1 and version v2 accepted".as_bytes());
+        
+        let masks = vec![Mask { regex: Regex::new(r"user=\w+").unwrap(), placeholder: "<*>" }];
+        let mask_config = Config { masks, ..config() };
+        assert_eq!(mask(&mask_config, "login user=alice ok".as_bytes()), "login <*> ok".as_bytes());
+        assert!(is_same(&mask_config, "login user=alice ok".as_bytes(), "login user=bob ok".as_bytes()));
+    }
+    
+    #[test]
+    fn test_compare_key_selected() {
+        let line = "2024-01-01 12:00:00 id=42 GET /orders\n";
+        
+        let key_config = Config { key_field: Some(3), ..config() };
+        assert_eq!(key(&key_config, line), "id=42");
+        
+        let key_config = Config { key_field: Some(2), delimiter: Some('='), ..config() };
+        assert_eq!(key(&key_config, line), "42 GET /orders");
+        
+        let key_config = Config { key_field: Some(9), ..config() };
+        assert_eq!(key(&key_config, line), "");
+        
+        let key_config = Config { key_regex: Some(Regex::new(r"id=(\d+)").unwrap()), ..config() };
+        assert_eq!(key(&key_config, line), "42");
+        assert_eq!(key(&key_config, "no id\n"), "no id");
+        
+        let key_config = Config { key_regex: Some(Regex::new(r"GET \S+").unwrap()), check_chars: Some(5), ..config() };
+        assert_eq!(key(&key_config, line), "GET /");
+        
+        let key_config = Config { key_field: Some(2), csv: true, ..config() };
+        assert_eq!(key(&key_config, "1,\"Smith, \"\"J\"\"\",x\n"), "Smith, \"J\"");
+    }
+    
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field(b"a,b,c", b",", 0), b"a");
+        assert_eq!(csv_field(b"a,b,c", b",", 2), b"c");
+        assert_eq!(csv_field(b"a,b,c", b",", 3), b"");
+        assert_eq!(csv_field(b"\"a;b\";c", b";", 0), b"a;b");
+        assert_eq!(csv_field(b"a,\"\",c", b",", 1), b"");
+        assert_eq!(csv_field("a→\"b→c\"→d".as_bytes(), "→".as_bytes(), 1), "b→c".as_bytes());
+    }
+    
+    #[test]
+    fn test_count_width() {
+        let input = format!("{}b\n", "a\n".repeat(12345));
+        let uniq = |config: &Config| {
+            let mut out = vec![];
+            uniq(config, input.as_bytes(), &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        
+        let count_config = Config { count: true, ..config() };
+        assert_eq!(uniq(&count_config), "12345 a\n    1 b\n");
+        
+        let count_config = Config { sort: Some(SortBy::Lexical), ..config() };
+        assert_eq!(uniq(&count_config), "12345 a\n    1 b\n");
+        
+        let count_config = Config { count: true, unique: true, ..config() };
+        assert_eq!(uniq(&count_config), "   1 b\n");
+        
+        // Rows already streamed out keep the narrower column.
+        let input = format!("b\n{}", "a\n".repeat(12345));
+        let mut out = vec![];
+        super::uniq(&Config { count: true, ..config() }, input.as_bytes(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "   1 b\n12345 a\n");
+    }
+    
+    #[test]
+    fn test_char_offset() {
+        assert_eq!(char_offset("日本語".as_bytes(), 0), Some(0));
+        assert_eq!(char_offset("日本語".as_bytes(), 2), Some(6));
+        assert_eq!(char_offset("日本語".as_bytes(), 3), None);
+        assert_eq!(char_offset(b"a\xff\xfeb", 2), Some(2));
+        assert_eq!(char_offset(b"a\xff\xfeb", 3), Some(3));
+    }
+    
+    #[test]
+    fn test_zero_terminated_bytes() {
+        let uniq = |config: &Config, input: &[u8]| {
+            let mut out = vec![];
+            uniq(config, input, &mut out).unwrap();
+            out
+        };
+        
+        let zero_config = Config { count: true, terminator: b'\0', ..config() };
+        assert_eq!(uniq(&zero_config, b"a\nb\0a\nb\0a \0c"), b"   2 a\nb\0   1 a \0   1 c");
+        
+        let bytes_config = config();
+        assert_eq!(uniq(&bytes_config, b"\xff\n\xff\n\xfe\n"), b"\xff\n\xfe\n");
+        
+        let bytes_config = Config { ignore_case: true, ..config() };
+        assert_eq!(uniq(&bytes_config, b"A\xff\na\xff\nb\xfe\n"), b"A\xff\nb\xfe\n");
+        
+        let global_config = Config { global: true, ignore_case: true, ..config() };
+        assert_eq!(uniq(&global_config, b"A\xff\nb\na\xff\nB\n"), b"A\xff\nb\n");
+        
+        let empty_config = Config { count: true, ..config() };
+        assert_eq!(uniq(&empty_config, b"\n\na\n"), b"   2 \n   1 a\n");
+    }
+    
+    #[test]
+    fn test_edit_distance() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(edit_distance(&chars("kitten"), &chars("sitting")), 3);
+        assert_eq!(edit_distance(&chars(""), &chars("abc")), 3);
+        assert_eq!(edit_distance(&chars("日本語"), &chars("日本")), 1);
+        assert_eq!(edit_distance(&chars("same"), &chars("same")), 0);
+    }
+    
+    #[test]
+    fn test_similarity() {
+        let edit_config = config();
+        assert_eq!(similarity(&edit_config, b"", b""), 1.0);
+        assert_eq!(similarity(&edit_config, b"abcd", b"abce"), 0.75);
+        assert_eq!(similarity(&edit_config, b"Hello", b"hello"), 0.8);
+        
+        let edit_config = Config { ignore_case: true, similarity: Some(0.9), ..config() };
+        assert_eq!(similarity(&edit_config, b"Hello", b"hello"), 1.0);
+        assert!(is_same(&edit_config, b"The quick brown fox.\n", b"the quick brown fox\n"));
+        assert!(!is_same(&edit_config, b"The quick brown fox\n", b"The lazy dog\n"));
+        
+        let exact_config = Config { ignore_case: true, similarity: Some(1.0), ..config() };
+        assert!(is_same(&exact_config, "Straße\n".as_bytes(), b"STRASSE\n"));
+        assert!(is_same(&Config { similarity: None, ..exact_config }, "Straße\n".as_bytes(), b"STRASSE\n"));
+        assert_eq!(similarity(&edit_config, "Straßen".as_bytes(), b"STRASSE"), 1.0 - 1.0 / 8.0);
+        
+        let jaccard_config = Config { metric: Metric::Jaccard, similarity: Some(0.5), ..config() };
+        assert_eq!(similarity(&jaccard_config, b"a b c", b"c b a"), 1.0);
+        assert_eq!(similarity(&jaccard_config, b"a b c", b"a b d"), 0.5);
+        assert!(is_same(&jaccard_config, b"red green blue\n", b"blue red yellow green\n"));
+        assert!(!is_same(&jaccard_config, b"red green blue\n", b"red black white\n"));
+    }
+}
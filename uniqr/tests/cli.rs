@@ -61,7 +61,7 @@ fn run_count(test: &Test) -> TestResult {
     // println!("##### run_count: expected: {}", expected);
     
     Command::new(cargo::cargo_bin!())
-        .args(&["-c", test.input])
+        .args(["-c", test.input])
         .assert()
         .success()
         .stdout(expected);
@@ -103,7 +103,7 @@ fn run_outfile(test: &Test) -> TestResult {
     let outfile_path = outfile.path().to_str().unwrap();
     
     Command::new(cargo::cargo_bin!())
-        .args(&["--count", test.input, outfile_path])
+        .args(["--count", test.input, outfile_path])
         .assert()
         .success()
         .stdout("");
@@ -122,6 +122,18 @@ fn run_outfile(test: &Test) -> TestResult {
     Ok(())
 }
 
+fn run_args(args: &[&str], expected_file: &str) -> TestResult {
+    let expected = std::fs::read(expected_file)?;
+    
+    Command::new(cargo::cargo_bin!())
+        .args(args)
+        .assert()
+        .success()
+        .stdout(expected);
+    
+    Ok(())
+}
+
 // ############################################################################
 
 #[cfg(test)]
@@ -243,4 +255,465 @@ mod test {
     }
     
     
-}
\ No newline at end of file
+    
+    // ############################################################################
+    
+    #[test]
+    fn three_repeated() -> TestResult {
+        run_args(&["-d", test_case::THREE.input], "tests/expected/three.txt.d.out")
+    }
+    
+    #[test]
+    fn three_repeated_count() -> TestResult {
+        run_args(&["--repeated", "-c", test_case::THREE.input], "tests/expected/three.txt.dc.out")
+    }
+    
+    #[test]
+    fn three_unique() -> TestResult {
+        run_args(&["-u", test_case::THREE.input], "tests/expected/three.txt.u.out")
+    }
+    
+    #[test]
+    fn three_unique_count() -> TestResult {
+        run_args(&["--unique", "--count", test_case::THREE.input], "tests/expected/three.txt.uc.out")
+    }
+    
+    #[test]
+    fn three_repeated_unique() -> TestResult {
+        run_args(&["-d", "-u", test_case::THREE.input], "tests/expected/empty.txt.out")
+    }
+    
+    #[test]
+    fn three_all_repeated() -> TestResult {
+        run_args(&["-D", test_case::THREE.input], "tests/expected/three.txt.D.out")
+    }
+    
+    #[test]
+    fn three_all_repeated_none() -> TestResult {
+        run_args(&["--all-repeated=none", test_case::THREE.input], "tests/expected/three.txt.D.out")
+    }
+    
+    #[test]
+    fn three_all_repeated_prepend() -> TestResult {
+        run_args(&["--all-repeated=prepend", test_case::THREE.input], "tests/expected/three.txt.D_prepend.out")
+    }
+    
+    #[test]
+    fn three_all_repeated_separate() -> TestResult {
+        run_args(&["--all-repeated=separate", test_case::THREE.input], "tests/expected/three.txt.D_separate.out")
+    }
+    
+    #[test]
+    fn three_all_repeated_count() -> TestResult {
+        run_args(&["-D", "-c", test_case::THREE.input], "tests/expected/three.txt.Dc.out")
+    }
+    
+    // ############################################################################
+    
+    #[test]
+    fn log_skip_fields() -> TestResult {
+        run_args(&["-f", "2", "tests/inputs/log.txt"], "tests/expected/log.txt.f2.out")
+    }
+    
+    #[test]
+    fn log_skip_fields_ignore_case_count() -> TestResult {
+        run_args(&["-c", "-f", "2", "-i", "tests/inputs/log.txt"], "tests/expected/log.txt.cf2i.out")
+    }
+    
+    #[test]
+    fn log_skip_chars_check_chars() -> TestResult {
+        run_args(&["-s", "20", "-w", "4", "tests/inputs/log.txt"], "tests/expected/log.txt.s20w4.out")
+    }
+    
+    #[test]
+    fn log_skip_fields_chars_check_chars_count() -> TestResult {
+        run_args(
+            &["--skip-fields=1", "--skip-chars=1", "--check-chars=2", "-c", "tests/inputs/log.txt"],
+            "tests/expected/log.txt.cf1s1w2.out",
+        )
+    }
+    
+    #[test]
+    fn three_global() -> TestResult {
+        run_args(&["--global", test_case::THREE.input], "tests/expected/three.txt.global.out")
+    }
+    
+    #[test]
+    fn three_global_spilled() -> TestResult {
+        run_args(&["--global", "--max-memory", "1", test_case::THREE.input], "tests/expected/three.txt.global.out")
+    }
+    
+    #[test]
+    fn log_global_skip_fields_ignore_case() -> TestResult {
+        run_args(&["--global", "-f", "2", "-i", "tests/inputs/log.txt"], "tests/expected/log.txt.global_f2i.out")
+    }
+    
+    #[test]
+    fn dies_global_count() -> TestResult {
+        Command::new(cargo::cargo_bin!())
+            .args(["--global", "-c", test_case::THREE.input])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("cannot be used with"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn dies_bad_max_memory() -> TestResult {
+        Command::new(cargo::cargo_bin!())
+            .args(["--global", "--max-memory", "1T", test_case::THREE.input])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("invalid --max-memory: '1T'"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn three_top() -> TestResult {
+        run_args(&["--top", "2", test_case::THREE.input], "tests/expected/three.txt.top2.out")
+    }
+    
+    #[test]
+    fn three_sort_lexical_percent_cumulative() -> TestResult {
+        run_args(
+            &["--sort=lexical", "--percent", "--cumulative", test_case::THREE.input],
+            "tests/expected/three.txt.lexical_percent_cumulative.out",
+        )
+    }
+    
+    #[test]
+    fn dies_bad_top() -> TestResult {
+        Command::new(cargo::cargo_bin!())
+            .args(["--top", "x", test_case::THREE.input])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("invalid number of lines to report: 'x'"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn app_mask() -> TestResult {
+        run_args(&["--mask=timestamp,ip,numbers", "tests/inputs/app.txt"], "tests/expected/app.txt.mask.out")
+    }
+    
+    #[test]
+    fn app_mask_all_sort() -> TestResult {
+        run_args(
+            &["--mask=timestamp,uuid,ip,numbers,hex", "--mask-regex", r"user=\w+", "--sort=count", "tests/inputs/app.txt"],
+            "tests/expected/app.txt.mask_all_sort.out",
+        )
+    }
+    
+    #[test]
+    fn mask_sort_unterminated_last_line() -> TestResult {
+        Command::new(cargo::cargo_bin!())
+            .args(["--mask=numbers", "--sort=lexical"])
+            .write_stdin("c\nb")
+            .assert()
+            .success()
+            .stdout("   1 b\tb\n   1 c\tc\n");
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn dies_bad_mask_regex() -> TestResult {
+        Command::new(cargo::cargo_bin!())
+            .args(["--mask-regex", "(", "tests/inputs/app.txt"])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("invalid --mask-regex"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn orders_key_field_csv_count() -> TestResult {
+        run_args(
+            &["--key-field", "2", "--csv", "-c", "tests/inputs/orders.csv"],
+            "tests/expected/orders.csv.key2_csv_c.out",
+        )
+    }
+    
+    #[test]
+    fn orders_key_field_keep_last() -> TestResult {
+        run_args(
+            &["--key-field", "1", "-t", ",", "--keep=last", "tests/inputs/orders.csv"],
+            "tests/expected/orders.csv.key1_last.out",
+        )
+    }
+    
+    #[test]
+    fn log_key_regex_count() -> TestResult {
+        run_args(&["--key-regex", "(INFO|WARN|ERROR)", "-c", "tests/inputs/log.txt"], "tests/expected/log.txt.key_regex_c.out")
+    }
+    
+    #[test]
+    fn dies_bad_key_field() -> TestResult {
+        Command::new(cargo::cargo_bin!())
+            .args(["--key-field", "0", "tests/inputs/log.txt"])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("invalid key field: '0'"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn dies_bad_delimiter() -> TestResult {
+        Command::new(cargo::cargo_bin!())
+            .args(["--key-field", "1", "-t", ",,", "tests/inputs/log.txt"])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains(r#"--delimiter ",," must be a single character"#));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn three_group() -> TestResult {
+        run_args(&["--group", test_case::THREE.input], "tests/expected/three.txt.group.out")
+    }
+    
+    #[test]
+    fn three_group_prepend() -> TestResult {
+        run_args(&["--group=prepend", test_case::THREE.input], "tests/expected/three.txt.group_prepend.out")
+    }
+    
+    #[test]
+    fn three_group_append() -> TestResult {
+        run_args(&["--group=append", test_case::THREE.input], "tests/expected/three.txt.group_append.out")
+    }
+    
+    #[test]
+    fn three_group_both() -> TestResult {
+        run_args(&["--group=both", test_case::THREE.input], "tests/expected/three.txt.group_both.out")
+    }
+    
+    #[test]
+    fn empty_group_both() -> TestResult {
+        run_args(&["--group=both", test_case::EMPTY.input], "tests/expected/empty.txt.out")
+    }
+    
+    #[test]
+    fn dies_group_count() -> TestResult {
+        Command::new(cargo::cargo_bin!())
+            .args(["--group", "-c", test_case::THREE.input])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("cannot be used with"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn multiple_inputs_output() -> TestResult {
+        let outfile = NamedTempFile::new()?;
+        let outfile_path = outfile.path().to_str().unwrap();
+        
+        Command::new(cargo::cargo_bin!())
+            .args(["-c", test_case::THREE.input, test_case::TWO.input, "-o", outfile_path])
+            .assert()
+            .success()
+            .stdout("");
+        
+        let expected = std::fs::read_to_string("tests/expected/three_two.txt.c.out")?;
+        assert_eq!(expected, std::fs::read_to_string(outfile_path)?);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn dies_non_empty_output_operand() -> TestResult {
+        let dir = tempfile::tempdir()?;
+        let two = dir.path().join("two.txt");
+        std::fs::copy(test_case::TWO.input, &two)?;
+        
+        Command::new(cargo::cargo_bin!())
+            .args(["-c", test_case::THREE.input, two.to_str().unwrap()])
+            .assert()
+            .failure()
+            .stdout("")
+            .stderr(predicates::str::contains("refusing to overwrite a non-empty OUTPUT"));
+        
+        assert_eq!(std::fs::read_to_string(test_case::TWO.input)?, std::fs::read_to_string(&two)?);
+        
+        let expected = std::fs::read_to_string("tests/expected/three_two.txt.c.out")?;
+        Command::new(cargo::cargo_bin!())
+            .args(["-c", test_case::THREE.input, two.to_str().unwrap(), "-o", "-"])
+            .assert()
+            .success()
+            .stdout(expected);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn unterminated_input_ends_its_line() -> TestResult {
+        let dir = tempfile::tempdir()?;
+        let f2 = dir.path().join("f2");
+        let f3 = dir.path().join("f3");
+        std::fs::write(&f2, "c")?;
+        std::fs::write(&f3, "c\n")?;
+        
+        Command::new(cargo::cargo_bin!())
+            .args(["-c", f2.to_str().unwrap(), f3.to_str().unwrap(), f2.to_str().unwrap(), "-o", "-"])
+            .assert()
+            .success()
+            .stdout("   3 c\n");
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn in_place() -> TestResult {
+        let dir = tempfile::tempdir()?;
+        let three = dir.path().join("three.txt");
+        let two = dir.path().join("two.txt");
+        std::fs::copy(test_case::THREE.input, &three)?;
+        std::fs::copy(test_case::TWO.input, &two)?;
+        
+        Command::new(cargo::cargo_bin!())
+            .args(["--in-place", "-c", three.to_str().unwrap(), two.to_str().unwrap()])
+            .assert()
+            .success()
+            .stdout("");
+        
+        assert_eq!(std::fs::read_to_string(test_case::THREE.out_count)?, std::fs::read_to_string(&three)?);
+        assert_eq!(std::fs::read_to_string(test_case::TWO.out_count)?, std::fs::read_to_string(&two)?);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn dies_output_is_input() -> TestResult {
+        let dir = tempfile::tempdir()?;
+        let three = dir.path().join("three.txt");
+        std::fs::copy(test_case::THREE.input, &three)?;
+        let three_path = three.to_str().unwrap();
+        
+        Command::new(cargo::cargo_bin!())
+            .args([three_path, "-o", three_path])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("output file is the same as input file"));
+        
+        Command::new(cargo::cargo_bin!())
+            .args([test_case::TWO.input, three_path, "--output", three_path])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("output file is the same as input file"));
+        
+        assert_eq!(std::fs::read_to_string(test_case::THREE.input)?, std::fs::read_to_string(&three)?);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn dies_in_place_stdin() -> TestResult {
+        Command::new(cargo::cargo_bin!())
+            .args(["--in-place", "-"])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("--in-place cannot rewrite standard input"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn dies_write_error() -> TestResult {
+        Command::new(cargo::cargo_bin!())
+            .args([test_case::THREE.input, "-o", "/dev/full"])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("No space left on device"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn three_json() -> TestResult {
+        run_args(&["--format=json", test_case::THREE.input], "tests/expected/three.txt.json.out")
+    }
+    
+    #[test]
+    fn three_repeated_jsonl() -> TestResult {
+        run_args(&["--format=jsonl", "-d", test_case::THREE.input], "tests/expected/three.txt.d_jsonl.out")
+    }
+    
+    #[test]
+    fn three_top_csv() -> TestResult {
+        run_args(
+            &["--format=csv", "--top", "2", "--percent", "--cumulative", test_case::THREE.input],
+            "tests/expected/three.txt.top2_csv.out",
+        )
+    }
+    
+    #[test]
+    fn dies_format_group() -> TestResult {
+        Command::new(cargo::cargo_bin!())
+            .args(["--format=json", "--group", test_case::THREE.input])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("--format cannot be used with"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn names_zero_terminated() -> TestResult {
+        run_args(&["-z", "tests/inputs/names0.txt"], "tests/expected/names0.txt.z.out")
+    }
+    
+    #[test]
+    fn names_zero_terminated_count() -> TestResult {
+        run_args(&["--zero-terminated", "-c", "tests/inputs/names0.txt"], "tests/expected/names0.txt.zc.out")
+    }
+    
+    #[test]
+    fn scraped_similarity_count() -> TestResult {
+        run_args(&["-c", "--similarity", "0.9", "tests/inputs/scraped.txt"], "tests/expected/scraped.txt.c_sim90.out")
+    }
+    
+    #[test]
+    fn scraped_similarity_ignore_case_count() -> TestResult {
+        run_args(
+            &["-c", "-i", "--similarity=0.9", "tests/inputs/scraped.txt"],
+            "tests/expected/scraped.txt.ci_sim90.out",
+        )
+    }
+    
+    #[test]
+    fn scraped_similarity_jaccard_count() -> TestResult {
+        run_args(
+            &["-c", "--similarity", "0.6", "--metric=jaccard", "tests/inputs/scraped.txt"],
+            "tests/expected/scraped.txt.c_jaccard60.out",
+        )
+    }
+    
+    #[test]
+    fn dies_bad_similarity() -> TestResult {
+        Command::new(cargo::cargo_bin!())
+            .args(["--similarity", "1.5", "tests/inputs/scraped.txt"])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("invalid similarity threshold: '1.5'"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn dies_bad_skip_fields() -> TestResult {
+        Command::new(cargo::cargo_bin!())
+            .args(["-f", "x", "tests/inputs/log.txt"])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("invalid number of fields to skip: 'x'"));
+        
+        Ok(())
+    }
+}
@@ -1,105 +1,952 @@
-use std::{error::Error, io::BufRead};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs::{self, File},
+    ffi::OsString,
+    io::{self, BufRead, BufReader, IsTerminal, Read, Seek, SeekFrom, Write},
+    iter::Sum,
+    ops::{Add, AddAssign, Range},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
 
 use clap::Arg;
+use ignore::{WalkBuilder, overrides::OverrideBuilder};
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
 
 pub type MyResult<T> = Result<T, Box<dyn Error>>;
 
+/// Regular files are only split into chunks for parallel counting when
+/// each chunk would be at least this large.
+const MIN_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
 #[derive(Debug)]
 pub struct Config {
-    files: Vec<String>,
+    files: Vec<PathBuf>,
     lines: bool,
     words: bool,
     bytes: bool,
     chars: bool,
+    graphemes: bool,
+    max_line_length: bool,
+    word_mode: WordMode,
+    format: OutputFormat,
+    jobs: usize,
+    /// The `--files0-from` file, `-` for stdin.
+    files0_from: Option<PathBuf>,
+    recursive: bool,
+    group_by: Option<GroupBy>,
+    includes: Vec<String>,
+    excludes: Vec<String>,
+    respect_ignore: bool,
+    progress: Option<Duration>,
+    pattern: Option<Regex>,
+    blank: bool,
+    comment_prefix: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    /// Subtotals per file extension.
+    Ext,
+    /// Subtotals per top-level directory below each directory argument.
+    Dir,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    /// A single JSON array of records.
+    Json,
+    /// One JSON object per line.
+    Jsonl,
+    Csv,
+    Tsv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WordMode {
+    /// Words are runs of non-whitespace characters.
+    Whitespace,
+    /// Words are determined by UAX #29 word boundaries.
+    Unicode,
 }
 
-#[derive(Debug, PartialEq)]
+/// The counts of one input, or the sum of several.
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct FileInfo {
     num_lines: usize,
     num_words: usize,
     num_bytes: usize,
     num_chars: usize,
+    num_graphemes: usize,
+    max_line_length: usize,
+    num_matching: usize,
+    num_blank: usize,
+    num_comment: usize,
+}
+
+impl FileInfo {
+    pub fn num_lines(&self) -> usize {
+        self.num_lines
+    }
+    
+    pub fn num_words(&self) -> usize {
+        self.num_words
+    }
+    
+    pub fn num_bytes(&self) -> usize {
+        self.num_bytes
+    }
+    
+    pub fn num_chars(&self) -> usize {
+        self.num_chars
+    }
+    
+    pub fn num_graphemes(&self) -> usize {
+        self.num_graphemes
+    }
+    
+    /// The display width of the longest line, see `-L`.
+    pub fn max_line_length(&self) -> usize {
+        self.max_line_length
+    }
+    
+    /// The number of lines matching the pattern, see `Counter::with_pattern`.
+    pub fn num_matching(&self) -> usize {
+        self.num_matching
+    }
+    
+    /// The number of empty or whitespace-only lines.
+    pub fn num_blank(&self) -> usize {
+        self.num_blank
+    }
+    
+    /// The number of comment lines, see `Counter::with_comment_prefix`.
+    pub fn num_comment(&self) -> usize {
+        self.num_comment
+    }
+}
+
+impl AddAssign<&FileInfo> for FileInfo {
+    fn add_assign(&mut self, other: &FileInfo) {
+        self.num_lines += other.num_lines;
+        self.num_words += other.num_words;
+        self.num_bytes += other.num_bytes;
+        self.num_chars += other.num_chars;
+        self.num_graphemes += other.num_graphemes;
+        self.max_line_length = self.max_line_length.max(other.max_line_length);
+        self.num_matching += other.num_matching;
+        self.num_blank += other.num_blank;
+        self.num_comment += other.num_comment;
+    }
+}
+
+impl Add for FileInfo {
+    type Output = FileInfo;
+    
+    fn add(mut self, other: FileInfo) -> FileInfo {
+        self += &other;
+        self
+    }
+}
+
+impl Sum for FileInfo {
+    fn sum<I: Iterator<Item = FileInfo>>(iter: I) -> FileInfo {
+        iter.fold(FileInfo::default(), Add::add)
+    }
+}
+
+impl<'a> Sum<&'a FileInfo> for FileInfo {
+    fn sum<I: Iterator<Item = &'a FileInfo>>(iter: I) -> FileInfo {
+        iter.fold(FileInfo::default(), |mut total, info| {
+            total += info;
+            total
+        })
+    }
+}
+
+/// Counts input that is fed in chunks of bytes, e.g. as it arrives from a
+/// socket. Chunks may split lines and multi-byte characters anywhere; the
+/// result is the same as that of `count` over the concatenated input.
+///
+/// Input need not be valid UTF-8: invalid bytes are not counted as
+/// characters, and the other text columns see them as U+FFFD.
+#[derive(Debug)]
+pub struct Counter {
+    word_mode: WordMode,
+    /// Which of the costlier columns are counted; lines and bytes always are.
+    words: bool,
+    chars: bool,
+    graphemes: bool,
+    max_line_length: bool,
+    blank: bool,
+    pattern: Option<Regex>,
+    comment_prefix: Option<String>,
+    info: FileInfo,
+    partial_line: Vec<u8>,
+}
+
+impl Counter {
+    pub fn new(word_mode: WordMode) -> Self {
+        Counter {
+            word_mode,
+            words: true,
+            chars: true,
+            graphemes: true,
+            max_line_length: true,
+            blank: true,
+            pattern: None,
+            comment_prefix: None,
+            info: FileInfo::default(),
+            partial_line: vec![],
+        }
+    }
+    
+    /// Also counts the lines matching `pattern`.
+    pub fn with_pattern(mut self, pattern: Regex) -> Self {
+        self.pattern = Some(pattern);
+        self
+    }
+    
+    /// Also counts the lines whose first non-whitespace characters are `prefix`.
+    pub fn with_comment_prefix(mut self, prefix: &str) -> Self {
+        self.comment_prefix = Some(prefix.to_string());
+        self
+    }
+    
+    /// Counts the complete lines in `chunk` and keeps the rest for the next
+    /// call.
+    pub fn update(&mut self, mut chunk: &[u8]) {
+        while let Some(pos) = memchr::memchr(b'\n', chunk) {
+            let (line, rest) = chunk.split_at(pos + 1);
+            if self.partial_line.is_empty() {
+                self.count_line(line);
+            } else {
+                self.partial_line.extend_from_slice(line);
+                let line = std::mem::take(&mut self.partial_line);
+                self.count_line(&line);
+            }
+            chunk = rest;
+        }
+        self.partial_line.extend_from_slice(chunk);
+    }
+    
+    /// The counts of the complete lines fed so far.
+    pub fn info(&self) -> &FileInfo {
+        &self.info
+    }
+    
+    /// Counts the final line, which need not end with a newline.
+    pub fn finish(mut self) -> FileInfo {
+        if !self.partial_line.is_empty() {
+            let line = std::mem::take(&mut self.partial_line);
+            self.count_line(&line);
+        }
+        self.info
+    }
+    
+    fn count_line(&mut self, line: &[u8]) {
+        let info = &mut self.info;
+        info.num_lines += 1;
+        info.num_bytes += line.len();
+        if self.chars {
+            info.num_chars += line.utf8_chunks().map(|chunk| chunk.valid().chars().count()).sum::<usize>();
+        }
+        if !(self.words || self.graphemes || self.max_line_length || self.blank
+            || self.pattern.is_some() || self.comment_prefix.is_some()) {
+            return;
+        }
+        
+        let line = &String::from_utf8_lossy(line);
+        if self.words {
+            info.num_words += match self.word_mode {
+                WordMode::Whitespace => line.split_whitespace().count(),
+                WordMode::Unicode => line.unicode_words().count(),
+            };
+        }
+        if self.graphemes {
+            info.num_graphemes += line.graphemes(true).count();
+        }
+        if self.max_line_length {
+            info.max_line_length = info.max_line_length.max(display_width(line));
+        }
+        
+        let text = line.strip_suffix('\n').unwrap_or(line);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        if self.blank && text.trim().is_empty() {
+            info.num_blank += 1;
+        }
+        if self.pattern.as_ref().is_some_and(|pattern| pattern.is_match(text)) {
+            info.num_matching += 1;
+        }
+        if self.comment_prefix.as_ref().is_some_and(|prefix| text.trim_start().starts_with(prefix.as_str())) {
+            info.num_comment += 1;
+        }
+    }
+}
+
+/// Periodically reports the running counts of the file being read to stderr.
+struct Progress {
+    interval: Duration,
+    is_tty: bool,
+    start: Instant,
+    last_report: Instant,
+}
+
+impl Progress {
+    fn new(interval: Duration) -> Self {
+        let now = Instant::now();
+        Progress { interval, is_tty: io::stderr().is_terminal(), start: now, last_report: now }
+    }
+    
+    fn start(&mut self) {
+        self.start = Instant::now();
+        self.last_report = self.start;
+    }
+    
+    fn report(&mut self, config: &Config, filename: &Path, info: &FileInfo) {
+        if self.last_report.elapsed() < self.interval {
+            return;
+        }
+        self.last_report = Instant::now();
+        
+        let counts: Vec<String> = columns(config, info).iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let throughput = if elapsed > 0.0 { info.num_bytes as f64 / elapsed / (1024.0 * 1024.0) } else { 0.0 };
+        let mut status = format!("{} ({:.1} MiB/s)", counts.join(" "), throughput);
+        if filename != Path::new("-") {
+            status = format!("{}: {}", filename.display(), status);
+        }
+        
+        if self.is_tty {
+            eprint!("\r\x1b[K{}", status);
+        } else {
+            eprintln!("{}", status);
+        }
+    }
+    
+    fn finish(&self) {
+        if self.is_tty {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
+
+/// A unit of work for parallel counting: a whole file, or a byte range of
+/// a regular file that starts and ends on a line boundary.
+struct Task {
+    file_index: usize,
+    range: Option<Range<u64>>,
 }
 
 pub fn run(config: Config) -> MyResult<()> {
+    run_to(config, &mut io::stdout().lock())
+}
+
+/// Same as `run`, writing the results to `out` instead of stdout.
+/// Errors for individual files are still reported on stderr.
+pub fn run_to(mut config: Config, out: &mut impl Write) -> MyResult<()> {
+    
+    let paths = if config.recursive {
+        let files = expand_paths(&config);
+        std::mem::replace(&mut config.files, files)
+    } else {
+        config.files.clone()
+    };
     
     let file_num = config.files.len();
-    let mut total_info = FileInfo {
-        num_lines: 0,
-        num_words: 0,
-        num_bytes: 0,
-        num_chars: 0,
+    let mut total_info = FileInfo::default();
+    
+    let mut progress = config.progress.map(Progress::new);
+    
+    let results: Box<dyn Iterator<Item = MyResult<FileInfo>>> = if config.jobs > 1 {
+        Box::new(count_parallel(&config).into_iter())
+    } else {
+        Box::new(config.files.iter().map(|filename| match progress.as_mut() {
+            None => count_path(&config, filename, &mut |_| {}),
+            Some(progress) => {
+                progress.start();
+                let result = count_path(&config, filename, &mut |info| progress.report(&config, filename, info));
+                progress.finish();
+                result
+            },
+        }))
     };
     
-    for filename in &config.files {
-        
-        match open(filename) {
+    let mut infos = vec![];
+    for (filename, result) in config.files.iter().zip(results) {
+        match result {
             Err(err) => eprintln!("{}", err),
-            Ok(file) => {
-                
-                if let Ok(info) = count(file) {
-                    show_result(&config, &info, filename);
-                    
-                    total_info.num_lines += info.num_lines;
-                    total_info.num_words += info.num_words;
-                    total_info.num_bytes += info.num_bytes;
-                    total_info.num_chars += info.num_chars;
-                }
+            Ok(info) => {
+                total_info += &info;
+                infos.push((filename.clone(), info));
             },
         }
     }
     
+    let width = column_width(&config, &infos);
+    
+    let infos: Vec<(String, FileInfo)> = match config.group_by {
+        Some(group_by) => {
+            let mut groups: BTreeMap<String, FileInfo> = BTreeMap::new();
+            for (filename, info) in &infos {
+                *groups.entry(group_key(group_by, &paths, filename)).or_default() += info;
+            }
+            groups.into_iter().collect()
+        },
+        None => infos.into_iter()
+            .map(|(filename, info)| (filename.to_string_lossy().into_owned(), info))
+            .collect(),
+    };
+    
+    show_header(out, &config)?;
+    
+    for (index, (filename, info)) in infos.iter().enumerate() {
+        show_result(out, &config, info, Some(filename), index, width)?;
+    }
+    
     if file_num > 1 {
-        show_result(&config, &total_info, "total");
+        show_result(out, &config, &total_info, None, infos.len(), width)?;
     }
     
+    show_footer(out, &config)?;
+    
     Ok(())
 }
 
-fn show_result(config: &Config, info: &FileInfo, filename: &str) {
+/// Replaces directory arguments with the files found below them, honoring
+/// the include/exclude globs and ignore files.
+fn expand_paths(config: &Config) -> Vec<PathBuf> {
+    let mut files = vec![];
+    
+    for path in &config.files {
+        if !path.is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+        
+        let walker = match build_walker(config, path) {
+            Ok(walker) => walker,
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                continue;
+            },
+        };
+        
+        for entry in walker {
+            match entry {
+                Err(err) => eprintln!("{}", err),
+                Ok(entry) => {
+                    if entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+                        files.push(entry.into_path());
+                    }
+                },
+            }
+        }
+    }
+    
+    files
+}
+
+fn build_walker(config: &Config, path: &Path) -> MyResult<ignore::Walk> {
+    let mut overrides = OverrideBuilder::new(path);
+    for glob in &config.includes {
+        overrides.add(glob)?;
+    }
+    for glob in &config.excludes {
+        overrides.add(&format!("!{}", glob))?;
+    }
+    
+    Ok(WalkBuilder::new(path)
+        .standard_filters(config.respect_ignore)
+        .require_git(false)
+        .overrides(overrides.build()?)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build())
+}
+
+/// Returns the name of the group `filename` is summed up in: its extension,
+/// or the first directory below the directory argument it was found in.
+fn group_key(group_by: GroupBy, paths: &[PathBuf], path: &Path) -> String {
+
+    match group_by {
+        GroupBy::Ext => match path.extension() {
+            Some(ext) => format!("*.{}", ext.to_string_lossy()),
+            None => "(none)".to_string(),
+        },
+        GroupBy::Dir => {
+            let dir = paths.iter()
+                .filter(|root| root.is_dir())
+                .find_map(|root| {
+                    let mut components = path.strip_prefix(root).ok()?.components();
+                    let first = components.next()?;
+                    match components.next() {
+                        Some(_) => Some(root.join(first)),
+                        None => Some(root.clone()),
+                    }
+                })
+                .unwrap_or_else(|| match path.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                    _ => PathBuf::from("."),
+                });
+            dir.to_string_lossy().into_owned()
+        },
+    }
+}
+
+/// Computes the width of the text columns the way GNU wc does: wide enough
+/// for the total size of the regular files, at least 7 when any input is
+/// not a regular file, and 1 when there is nothing to align or the names
+/// were read from stdin or another stream.
+fn column_width(config: &Config, infos: &[(PathBuf, FileInfo)]) -> usize {
+    let num_columns = columns(config, &FileInfo::default()).len();
+    let streamed_names = config.files0_from.as_deref()
+        .is_some_and(|files0_from| files0_from == Path::new("-") || !is_regular_input(files0_from));
+    if streamed_names || (num_columns == 1 && config.files.len() == 1) {
+        return 1;
+    }
+    
+    let mut min_width = 1;
+    let mut regular_total = 0;
+    for (filename, info) in infos {
+        if is_regular_input(filename) {
+            regular_total += info.num_bytes;
+        } else {
+            min_width = 7;
+        }
+    }
+    
+    regular_total.to_string().len().max(min_width)
+}
+
+fn is_regular_input(filename: &Path) -> bool {
+    let path = if filename == Path::new("-") { Path::new("/dev/stdin") } else { filename };
+    fs::metadata(path).map(|metadata| metadata.is_file()).unwrap_or(false)
+}
+
+/// Opens and counts one file. `report` is called with the running counts
+/// while the file is being read.
+fn count_path(config: &Config, filename: &Path, report: &mut dyn FnMut(&FileInfo)) -> MyResult<FileInfo> {
+    let file = open(filename)?;
+    count_file(config, filename, file, report).map_err(|e| format!("{}: {}", filename.display(), e).into())
+}
+
+/// Counts one opened file, using its size from the metadata when only
+/// bytes were requested.
+fn count_file(
+    config: &Config,
+    filename: &Path,
+    file: impl BufRead,
+    report: &mut dyn FnMut(&FileInfo),
+) -> MyResult<FileInfo> {
+    if is_lines_bytes_only(config) && !config.lines
+        && let Some(num_bytes) = regular_file_size(filename) {
+        return Ok(FileInfo { num_bytes, ..FileInfo::default() });
+    }
+    
+    count_reader(config, file, report)
+}
+
+/// Counts the input, taking the fast path when only lines and/or bytes
+/// were requested.
+fn count_reader(config: &Config, file: impl BufRead, report: &mut dyn FnMut(&FileInfo)) -> MyResult<FileInfo> {
+    if is_lines_bytes_only(config) {
+        count_lines_bytes_with(file, report)
+    } else {
+        count_with(file, new_counter(config), report)
+    }
+}
+
+fn is_lines_bytes_only(config: &Config) -> bool {
+    !(config.words || config.chars || config.graphemes || config.max_line_length
+        || config.pattern.is_some() || config.blank || config.comment_prefix.is_some())
+}
+
+/// Builds a counter for the columns selected in `config` only.
+fn new_counter(config: &Config) -> Counter {
+    let mut counter = Counter {
+        words: config.words,
+        chars: config.chars,
+        graphemes: config.graphemes,
+        max_line_length: config.max_line_length,
+        blank: config.blank,
+        ..Counter::new(config.word_mode)
+    };
+    if let Some(pattern) = &config.pattern {
+        counter = counter.with_pattern(pattern.clone());
+    }
+    if let Some(prefix) = &config.comment_prefix {
+        counter = counter.with_comment_prefix(prefix);
+    }
+    counter
+}
+
+/// Counts the files on `config.jobs` threads. Large regular files are split
+/// into chunks that are counted concurrently and summed up again, so the
+/// results are returned in the order of `config.files`.
+fn count_parallel(config: &Config) -> Vec<MyResult<FileInfo>> {
+    let mut tasks = vec![];
+    
+    for (file_index, filename) in config.files.iter().enumerate() {
+        let num_chunks = match regular_file_size(filename) {
+            Some(_) if is_lines_bytes_only(config) && !config.lines => 1,
+            Some(size) => (size / MIN_CHUNK_SIZE).min(config.jobs),
+            None => 1,
+        };
+        
+        match chunk_ranges(filename, num_chunks) {
+            Ok(ranges) if ranges.len() > 1 => {
+                tasks.extend(ranges.into_iter().map(|range| Task { file_index, range: Some(range) }));
+            },
+            _ => tasks.push(Task { file_index, range: None }),
+        }
+    }
+    
+    let next_task = AtomicUsize::new(0);
+    let mut task_results: Vec<(usize, Result<FileInfo, String>)> = thread::scope(|s| {
+        let workers: Vec<_> = (0..config.jobs.min(tasks.len()))
+            .map(|_| s.spawn(|| {
+                let mut results = vec![];
+                loop {
+                    let index = next_task.fetch_add(1, Ordering::Relaxed);
+                    let Some(task) = tasks.get(index) else {
+                        break;
+                    };
+                    let result = run_task(config, task).map_err(|e| e.to_string());
+                    results.push((index, result));
+                }
+                results
+            }))
+            .collect();
+        
+        workers.into_iter()
+            .flat_map(|worker| worker.join().expect("counting thread panicked"))
+            .collect()
+    });
+    task_results.sort_by_key(|(index, _)| *index);
+    
+    let mut results: Vec<Result<FileInfo, String>> = config.files.iter().map(|_| Ok(FileInfo::default())).collect();
+    for (index, result) in task_results {
+        let file_result = &mut results[tasks[index].file_index];
+        match (file_result.as_mut(), result) {
+            (Ok(info), Ok(chunk_info)) => *info += &chunk_info,
+            (Ok(_), Err(err)) => *file_result = Err(err),
+            (Err(_), _) => {},
+        }
+    }
+    
+    results.into_iter().map(|result| result.map_err(From::from)).collect()
+}
+
+fn run_task(config: &Config, task: &Task) -> MyResult<FileInfo> {
+    let filename = &config.files[task.file_index];
+    match &task.range {
+        None => count_path(config, filename, &mut |_| {}),
+        Some(range) => {
+            let mut file = File::open(filename)?;
+            file.seek(SeekFrom::Start(range.start))?;
+            let chunk = BufReader::new(file.take(range.end - range.start));
+            count_reader(config, chunk, &mut |_| {}).map_err(|e| format!("{}: {}", filename.display(), e).into())
+        },
+    }
+}
+
+/// Splits a regular file into at most `num_chunks` byte ranges of similar
+/// size. Every range except the last ends just after a newline, so no line
+/// (and therefore no word or character) spans two chunks.
+fn chunk_ranges(filename: &Path, num_chunks: usize) -> MyResult<Vec<Range<u64>>> {
+    let mut file = BufReader::new(File::open(filename)?);
+    let size = file.get_ref().metadata()?.len();
+    let num_chunks = num_chunks.max(1) as u64;
+    
+    let mut ranges = vec![];
+    let mut start = 0;
+    
+    for i in 1..num_chunks {
+        let target = size * i / num_chunks;
+        if target <= start {
+            continue;
+        }
+        
+        file.seek(SeekFrom::Start(target - 1))?;
+        let end = target - 1 + skip_line(&mut file)?;
+        if end >= size {
+            break;
+        }
+        ranges.push(start..end);
+        start = end;
+    }
+    ranges.push(start..size);
+    
+    Ok(ranges)
+}
+
+/// Consumes input up to and including the next newline and returns the
+/// number of bytes consumed.
+fn skip_line(file: &mut impl BufRead) -> io::Result<u64> {
+    let mut skipped = 0;
+    loop {
+        let buf = file.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(skipped);
+        }
+        match memchr::memchr(b'\n', buf) {
+            Some(pos) => {
+                file.consume(pos + 1);
+                return Ok(skipped + pos as u64 + 1);
+            },
+            None => {
+                let len = buf.len();
+                file.consume(len);
+                skipped += len as u64;
+            },
+        }
+    }
+}
+
+/// Returns the size of a regular file. A size of 0 is not trusted, as files
+/// such as those in /proc report it while still having contents to read.
+fn regular_file_size(filename: &Path) -> Option<usize> {
+    if filename == Path::new("-") {
+        return None;
+    }
+    fs::metadata(filename).ok()
+        .filter(|metadata| metadata.is_file() && metadata.len() > 0)
+        .map(|metadata| metadata.len() as usize)
+}
+
+/// Returns the selected columns of `info` as (field name, value) pairs,
+/// in the order they are printed.
+fn columns(config: &Config, info: &FileInfo) -> Vec<(&'static str, usize)> {
+    let mut columns = vec![];
     if config.lines {
-        print!("{:>8}", info.num_lines);
+        columns.push(("lines", info.num_lines));
     }
     if config.words {
-        print!("{:>8}", info.num_words);
+        columns.push(("words", info.num_words));
     }
     if config.bytes {
-        print!("{:>8}", info.num_bytes);
+        columns.push(("bytes", info.num_bytes));
     }
     if config.chars {
-        print!("{:>8}", info.num_chars);
+        columns.push(("chars", info.num_chars));
     }
-    if filename != "-" {
-        println!(" {}", filename);
-    } else {
-        println!();
+    if config.graphemes {
+        columns.push(("graphemes", info.num_graphemes));
+    }
+    if config.max_line_length {
+        columns.push(("max_line_length", info.max_line_length));
+    }
+    if config.pattern.is_some() {
+        columns.push(("matching", info.num_matching));
+    }
+    if config.blank {
+        columns.push(("blank", info.num_blank));
     }
+    if config.comment_prefix.is_some() {
+        columns.push(("comment", info.num_comment));
+    }
+    columns
 }
 
-pub fn count(mut file: impl BufRead) -> MyResult<FileInfo> {
-    let mut num_lines = 0;
-    let mut num_words = 0;
-    let mut num_bytes = 0;
-    let mut num_chars = 0;
-    
-    let mut line = String::new();
+fn csv_writer<W: Write>(out: W, config: &Config) -> csv::Writer<W> {
+    let delimiter = if config.format == OutputFormat::Tsv { b'\t' } else { b',' };
+    csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(out)
+}
+
+fn show_header(out: &mut impl Write, config: &Config) -> MyResult<()> {
+    match config.format {
+        OutputFormat::Json => write!(out, "[")?,
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let mut header: Vec<&str> = columns(config, &FileInfo::default()).iter().map(|(name, _)| *name).collect();
+            header.push("filename");
+            header.push("type");
+            csv_writer(out, config).write_record(header)?;
+        },
+        OutputFormat::Text | OutputFormat::Jsonl => {},
+    }
+    Ok(())
+}
+
+/// Prints one result. `filename` is `None` for the total row, `index`
+/// is the number of results already printed and `width` is the width of
+/// the text columns.
+fn show_result(
+    out: &mut impl Write,
+    config: &Config,
+    info: &FileInfo,
+    filename: Option<&str>,
+    index: usize,
+    width: usize,
+) -> MyResult<()> {
+    let columns = columns(config, info);
+    // Tells machine-readable consumers the total row from a file named "total".
+    let kind = match filename {
+        None => "total",
+        Some(_) if config.group_by.is_some() => "group",
+        Some(_) => "file",
+    };
+    let name = filename.unwrap_or("total");
     
+    match config.format {
+        OutputFormat::Text => {
+            let values: Vec<String> = columns.iter().map(|(_, value)| format!("{:>width$}", value)).collect();
+            write!(out, "{}", values.join(" "))?;
+            match filename {
+                Some("-") => writeln!(out)?,
+                Some(filename) => writeln!(out, " {}", filename)?,
+                None => writeln!(out, " total")?,
+            }
+        },
+        OutputFormat::Json | OutputFormat::Jsonl => {
+            let mut record = serde_json::Map::new();
+            record.insert("filename".to_string(), name.into());
+            record.insert("type".to_string(), kind.into());
+            for (name, value) in columns {
+                record.insert(name.to_string(), value.into());
+            }
+            let record = serde_json::Value::Object(record);
+            
+            if config.format == OutputFormat::Json {
+                let separator = if index == 0 { "" } else { "," };
+                write!(out, "{}\n  {}", separator, record)?;
+            } else {
+                writeln!(out, "{}", record)?;
+            }
+        },
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let mut record: Vec<String> = columns.iter().map(|(_, value)| value.to_string()).collect();
+            record.push(name.to_string());
+            record.push(kind.to_string());
+            csv_writer(out, config).write_record(record)?;
+        },
+    }
+    Ok(())
+}
+
+fn show_footer(out: &mut impl Write, config: &Config) -> MyResult<()> {
+    if config.format == OutputFormat::Json {
+        writeln!(out, "\n]")?;
+    }
+    Ok(())
+}
+
+/// Counts the whole input, with words delimited by whitespace. See `Counter`
+/// for how invalid UTF-8 is counted.
+pub fn count(file: impl BufRead) -> MyResult<FileInfo> {
+    count_with_word_mode(file, WordMode::Whitespace)
+}
+
+/// Same as `count`, with words delimited according to `word_mode`.
+pub fn count_with_word_mode(file: impl BufRead, word_mode: WordMode) -> MyResult<FileInfo> {
+    count_with(file, Counter::new(word_mode), &mut |_| {})
+}
+
+/// Same as `count`, calling `report` with the running counts after each
+/// line.
+fn count_with(mut file: impl BufRead, mut counter: Counter, report: &mut dyn FnMut(&FileInfo)) -> MyResult<FileInfo> {
     loop {
-        let line_bytes = file.read_line(&mut line)?;
-        if line_bytes == 0 {
+        let buf = match file.fill_buf() {
+            Ok(buf) => buf,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        if buf.is_empty() {
             break;
         }
-        num_lines += 1;
-        num_words += line.split_whitespace().count();
-        num_bytes += line_bytes;
-        num_chars += line.chars().count();
-        line.clear();
-    }   
+        for line in buf.split_inclusive(|&byte| byte == b'\n') {
+            counter.update(line);
+            report(counter.info());
+        }
+        let len = buf.len();
+        file.consume(len);
+    }
     
-    Ok(FileInfo { num_lines, num_words, num_bytes, num_chars })
+    Ok(counter.finish())
 }
 
+/// Counts only lines and bytes, without decoding the input.
+/// The results are the same as those of `count`: a final line without
+/// a trailing newline is still counted as a line.
+pub fn count_lines_bytes(file: impl Read) -> MyResult<FileInfo> {
+    count_lines_bytes_with(file, &mut |_| {})
+}
+
+/// Same as `count_lines_bytes`, calling `report` with the running counts
+/// after each buffer.
+fn count_lines_bytes_with(mut file: impl Read, report: &mut dyn FnMut(&FileInfo)) -> MyResult<FileInfo> {
+    let mut info = FileInfo::default();
+    let mut last_byte = b'\n';
+    
+    let mut buf = vec![0; 128 * 1024];
+    
+    loop {
+        let bytes = match file.read(&mut buf) {
+            Ok(0) => break,
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        info.num_lines += memchr::memchr_iter(b'\n', &buf[..bytes]).count();
+        info.num_bytes += bytes;
+        last_byte = buf[bytes - 1];
+        report(&info);
+    }
+    
+    if last_byte != b'\n' {
+        info.num_lines += 1;
+    }
+    
+    Ok(info)
+}
+
+/// Returns the number of terminal columns the line occupies, like `wc -L`.
+/// Wide characters count as 2, tabs advance to the next multiple of 8 and
+/// carriage returns / form feeds move back to column 0.
+fn display_width(line: &str) -> usize {
+    let mut max_width = 0;
+    let mut width = 0;
+    
+    for c in line.chars() {
+        match c {
+            '\t' => width += 8 - width % 8,
+            '\n' | '\r' | '\x0c' => {
+                max_width = max_width.max(width);
+                width = 0;
+            },
+            _ => width += c.width().unwrap_or(0),
+        }
+    }
+    
+    max_width.max(width)
+}
+
+/// Parses the command line of the process, exiting on invalid arguments or
+/// `--help` the way clap does.
 pub fn get_args() -> MyResult<Config> {
+    get_args_from(std::env::args_os()).map_err(|err| match err.downcast::<clap::Error>() {
+        Ok(err) => err.exit(),
+        Err(err) => err,
+    })
+}
+
+/// Parses the given command line, whose first item is the program name.
+/// Invalid arguments, `--help` and `--version` are returned as a
+/// `clap::Error` instead of exiting the process.
+pub fn get_args_from<I, T>(args: I) -> MyResult<Config>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
     
     let matches = clap::App::new("wcr")
         .version("0.1.0")
@@ -141,39 +988,276 @@ pub fn get_args() -> MyResult<Config> {
                 .takes_value(false)
                 .conflicts_with("bytes")
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("graphemes")
+                .long("graphemes")
+                .help("Show grapheme cluster count")
+                .takes_value(false)
+        )
+        .arg(
+            Arg::with_name("max_line_length")
+                .short("L")
+                .long("max-line-length")
+                .help("Show maximum line display width")
+                .takes_value(false)
+        )
+        .arg(
+            Arg::with_name("files0_from")
+                .value_name("F")
+                .long("files0-from")
+                .help("Read input file names from NUL-terminated file F (- for stdin)")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("format")
+                .value_name("FORMAT")
+                .long("format")
+                .help("Output format")
+                .possible_values(&["text", "json", "jsonl", "csv", "tsv"])
+                .default_value("text")
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .value_name("N")
+                .short("j")
+                .long("jobs")
+                .help("Number of files or chunks to count in parallel")
+                .default_value("1")
+        )
+        .arg(
+            Arg::with_name("recursive")
+                .short("r")
+                .long("recursive")
+                .help("Count the files below directory arguments")
+                .takes_value(false)
+        )
+        .arg(
+            Arg::with_name("group_by")
+                .value_name("KEY")
+                .long("group-by")
+                .help("Print subtotals per file extension or top-level directory")
+                .possible_values(&["ext", "dir"])
+        )
+        .arg(
+            Arg::with_name("include")
+                .value_name("GLOB")
+                .long("include")
+                .help("Only count files matching GLOB when recursing")
+                .multiple(true)
+                .number_of_values(1)
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .value_name("GLOB")
+                .long("exclude")
+                .help("Skip files matching GLOB when recursing")
+                .multiple(true)
+                .number_of_values(1)
+        )
+        .arg(
+            Arg::with_name("no_ignore")
+                .long("no-ignore")
+                .help("Do not skip hidden files and files listed in .gitignore when recursing")
+                .takes_value(false)
+        )
+        .arg(
+            Arg::with_name("progress")
+                .value_name("INTERVAL")
+                .long("progress")
+                .help("Report running counts to stderr every INTERVAL seconds [default: 1], without --jobs")
+                .min_values(0)
+                .require_equals(true)
+        )
+        .arg(
+            Arg::with_name("pattern")
+                .value_name("REGEX")
+                .long("match")
+                .help("Show count of lines matching REGEX")
+        )
+        .arg(
+            Arg::with_name("blank")
+                .long("blank")
+                .help("Show count of empty or whitespace-only lines")
+                .takes_value(false)
+        )
+        .arg(
+            Arg::with_name("comment_prefix")
+                .value_name("STR")
+                .long("comment-prefix")
+                .help("Show count of lines starting with STR after leading whitespace")
+        )
+        .arg(
+            Arg::with_name("word_mode")
+                .value_name("MODE")
+                .long("word-mode")
+                .help("How words are delimited")
+                .possible_values(&["whitespace", "unicode"])
+                .default_value("whitespace")
+        )
+        .get_matches_from_safe(args)?;
     
     let mut param_lines = matches.is_present("lines");
     let mut param_words = matches.is_present("words");
     let mut param_bytes = matches.is_present("bytes");
     let param_chars = matches.is_present("chars"); 
+    let param_graphemes = matches.is_present("graphemes");
+    let param_max_line_length = matches.is_present("max_line_length");
+    let param_blank = matches.is_present("blank");
+    
+    let pattern = matches
+        .value_of("pattern")
+        .map(|pattern| Regex::new(pattern).map_err(|_| format!("Invalid --match \"{}\"", pattern)))
+        .transpose()?;
+    let comment_prefix = matches.value_of("comment_prefix").map(String::from);
+    
+    let word_mode = match matches.value_of("word_mode") {
+        Some("unicode") => WordMode::Unicode,
+        _ => WordMode::Whitespace,
+    };
+    
+    let format = match matches.value_of("format") {
+        Some("json") => OutputFormat::Json,
+        Some("jsonl") => OutputFormat::Jsonl,
+        Some("csv") => OutputFormat::Csv,
+        Some("tsv") => OutputFormat::Tsv,
+        _ => OutputFormat::Text,
+    };
     
-    if [param_lines, param_words, param_bytes, param_chars].iter().all(|v| v == &false) {
+    if [param_lines, param_words, param_bytes, param_chars, param_graphemes, param_max_line_length, param_blank]
+        .iter().all(|v| v == &false) && pattern.is_none() && comment_prefix.is_none() {
         param_lines = true;
         param_words = true;
         param_bytes = true;
     }
     
+    let jobs = matches
+        .value_of("jobs")
+        .map(parse_positive_int)
+        .transpose()
+        .map_err(|e| format!("invalid number of jobs -- {}", e))?
+        .unwrap();
+    
+    let group_by = match matches.value_of("group_by") {
+        Some("ext") => Some(GroupBy::Ext),
+        Some("dir") => Some(GroupBy::Dir),
+        _ => None,
+    };
+    
+    let includes = matches.values_of_lossy("include").unwrap_or_default();
+    let excludes = matches.values_of_lossy("exclude").unwrap_or_default();
+    for glob in includes.iter().chain(&excludes) {
+        OverrideBuilder::new(".")
+            .add(glob)
+            .map_err(|e| format!("invalid glob \"{}\": {}", glob, e))?;
+    }
+    
+    let progress = if matches.is_present("progress") {
+        let interval = matches.value_of("progress").unwrap_or("1");
+        match interval.parse::<f64>() {
+            Ok(secs) if secs >= 0.0 && secs.is_finite() => Some(Duration::from_secs_f64(secs)),
+            _ => return Err(format!("invalid progress interval -- {}", interval).into()),
+        }
+    } else {
+        None
+    };
+    if progress.is_some() && jobs > 1 {
+        return Err("--progress cannot be used with --jobs greater than 1".into());
+    }
+    
+    let files0_from = matches.value_of_os("files0_from").map(PathBuf::from);
+    let files = match files0_from.as_deref() {
+        Some(files0_from) => {
+            if matches.occurrences_of("files") > 0 {
+                return Err("file operands cannot be combined with --files0-from".into());
+            }
+            let file = open(files0_from)
+                .map_err(|e| format!("--files0-from: {}", e))?;
+            read_files0(file)?
+        },
+        None => matches.values_of_os("files").unwrap().map(PathBuf::from).collect(),
+    };
+    
     Ok(Config {
-        files: matches.values_of_lossy("files").unwrap(),
+        files,
         lines: param_lines,
         words: param_words,
         bytes: param_bytes,
         chars: param_chars,
+        graphemes: param_graphemes,
+        max_line_length: param_max_line_length,
+        word_mode,
+        format,
+        jobs,
+        files0_from,
+        recursive: matches.is_present("recursive"),
+        group_by,
+        includes,
+        excludes,
+        respect_ignore: !matches.is_present("no_ignore"),
+        progress,
+        pattern,
+        blank: param_blank,
+        comment_prefix,
     })
 }
 
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(std::io::BufReader::new(std::io::stdin()))),
-        _ => Ok(Box::new(std::io::BufReader::new(std::fs::File::open(filename)?))),
+fn parse_positive_int(val: &str) -> MyResult<usize> {
+    match val.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(From::from(val)),
+    }
+}
+
+fn open(filename: &Path) -> MyResult<Box<dyn BufRead>> {
+    match filename.to_str() {
+        Some("") => Err("invalid zero-length file name".into()),
+        Some("-") => Ok(Box::new(std::io::BufReader::new(std::io::stdin()))),
+        _ => Ok(Box::new(std::io::BufReader::new(
+            std::fs::File::open(filename).map_err(|e| format!("{}: {}", filename.display(), e))?
+        ))),
     }
 }
 
+/// Reads a list of NUL-terminated file names, as produced by `find -print0`.
+/// Zero-length names are kept so that they are reported when opened.
+fn read_files0(mut file: impl BufRead) -> MyResult<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut name = vec![];
+    
+    loop {
+        let bytes = file.read_until(b'\0', &mut name)?;
+        if bytes == 0 {
+            break;
+        }
+        if name.last() == Some(&b'\0') {
+            name.pop();
+        }
+        files.push(path_from_bytes(&name));
+        name.clear();
+    }
+    
+    Ok(files)
+}
+
+/// Names are arbitrary bytes on Unix, so they are kept as they are.
+#[cfg(unix)]
+fn path_from_bytes(name: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(name))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(name: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(name).into_owned())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{FileInfo, count};
-    use std::io::Cursor;
+    use super::{
+        Counter, FileInfo, GroupBy, WordMode, chunk_ranges, count, count_lines_bytes, count_with_word_mode, display_width,
+        get_args_from, group_key, new_counter, parse_positive_int, read_files0, run_to,
+    };
+    use std::{io::Cursor, path::{Path, PathBuf}};
     
     #[test]
     fn test_count() {
@@ -186,8 +1270,208 @@ mod tests {
             num_words: 10,
             num_bytes: 48,
             num_chars: 48,
+            num_graphemes: 47,
+            max_line_length: 46,
+            num_matching: 0,
+            num_blank: 0,
+            num_comment: 0,
         };
         
         assert_eq!(info_result.unwrap(), expected);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_count_unicode() {
+        let text = "日本語の文章です。\n";
+        
+        let info = count(Cursor::new(text)).unwrap();
+        assert_eq!(info.num_words, 1);
+        
+        let info = count_with_word_mode(Cursor::new(text), WordMode::Unicode).unwrap();
+        assert_eq!(info.num_words, 8);
+        assert_eq!(info.num_chars, 10);
+        assert_eq!(info.num_graphemes, 10);
+        
+        // "e" + combining acute accent is one grapheme but two chars
+        let info = count_with_word_mode(Cursor::new("cafe\u{301}\n"), WordMode::Unicode).unwrap();
+        assert_eq!(info.num_words, 1);
+        assert_eq!(info.num_chars, 6);
+        assert_eq!(info.num_graphemes, 5);
+    }
+    
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width(""), 0);
+        assert_eq!(display_width("abc\n"), 3);
+        assert_eq!(display_width("日本語\n"), 6);
+        assert_eq!(display_width("Rust日本\r\n"), 8);
+        assert_eq!(display_width("a\tb"), 9);
+        assert_eq!(display_width("\t\t"), 16);
+        assert_eq!(display_width("12345678\tx"), 17);
+        assert_eq!(display_width("long line\rab"), 9);
+    }
+    
+    #[test]
+    fn test_read_files0() {
+        let files = read_files0(Cursor::new("a.txt\0dir/b c.txt\0")).unwrap();
+        assert_eq!(files, [Path::new("a.txt"), Path::new("dir/b c.txt")]);
+        
+        let files = read_files0(Cursor::new("a.txt\0\0last")).unwrap();
+        assert_eq!(files, [Path::new("a.txt"), Path::new(""), Path::new("last")]);
+        
+        let files = read_files0(Cursor::new("")).unwrap();
+        assert!(files.is_empty());
+    }
+    
+    #[test]
+    fn test_count_lines_bytes() {
+        let long_line = "x".repeat(300 * 1024);
+        let many_lines = "line\n".repeat(100 * 1024);
+        let texts: [&[u8]; 9] = [
+            b"",
+            b"a",
+            b"a\n",
+            b"a\nb",
+            b"\n\n",
+            "日本語\r\nRust".as_bytes(),
+            b"abc\xff\n",
+            long_line.as_bytes(),
+            many_lines.as_bytes(),
+        ];
+        
+        for text in texts {
+            let info = count(Cursor::new(text)).unwrap();
+            let fast = count_lines_bytes(Cursor::new(text)).unwrap();
+            assert_eq!(fast.num_lines, info.num_lines, "{:?}", text);
+            assert_eq!(fast.num_bytes, info.num_bytes, "{:?}", text);
+        }
+    }
+    
+    #[test]
+    fn test_chunk_ranges() {
+        let filename = Path::new("tests/inputs/atlamal.txt");
+        let text = std::fs::read_to_string(filename).unwrap();
+        let whole = count(Cursor::new(&text)).unwrap();
+        
+        for num_chunks in 1..=6 {
+            let ranges = chunk_ranges(filename, num_chunks).unwrap();
+            assert!(ranges.len() <= num_chunks);
+            assert_eq!(ranges.first().unwrap().start, 0);
+            assert_eq!(ranges.last().unwrap().end, text.len() as u64);
+            
+            let mut total = FileInfo::default();
+            for range in ranges {
+                let chunk = &text[range.start as usize..range.end as usize];
+                total += &count(Cursor::new(chunk)).unwrap();
+            }
+            assert_eq!(total, whole);
+        }
+    }
+    
+    #[test]
+    fn test_parse_positive_int() {
+        assert_eq!(parse_positive_int("4").unwrap(), 4);
+        assert!(parse_positive_int("0").is_err());
+        assert!(parse_positive_int("-1").is_err());
+        assert!(parse_positive_int("a").is_err());
+    }
+    
+    #[test]
+    fn test_group_key() {
+        let paths = [PathBuf::from("src"), PathBuf::from("Cargo.toml")];
+        
+        assert_eq!(group_key(GroupBy::Ext, &paths, Path::new("src/lib.rs")), "*.rs");
+        assert_eq!(group_key(GroupBy::Ext, &paths, Path::new("Makefile")), "(none)");
+        
+        assert_eq!(group_key(GroupBy::Dir, &paths, Path::new("src/main.rs")), "src");
+        assert_eq!(group_key(GroupBy::Dir, &paths, Path::new("Cargo.toml")), ".");
+        assert_eq!(group_key(GroupBy::Dir, &paths, Path::new("tests/inputs/fox.txt")), "tests/inputs");
+    }
+    
+    #[test]
+    fn test_counter() {
+        let text = "日本語の文章です。\nThe  quick brown fox\r\nno newline";
+        let expected = count_with_word_mode(Cursor::new(text), WordMode::Unicode).unwrap();
+        
+        for chunk_size in 1..=text.len() {
+            let mut counter = Counter::new(WordMode::Unicode);
+            for chunk in text.as_bytes().chunks(chunk_size) {
+                counter.update(chunk);
+            }
+            assert_eq!(counter.finish(), expected, "chunk size {}", chunk_size);
+        }
+        
+        let mut counter = Counter::new(WordMode::Whitespace);
+        counter.update(b"one two\nthree");
+        assert_eq!(counter.info().num_lines(), 1);
+        assert_eq!(counter.info().num_words(), 2);
+        
+        let mut counter = Counter::new(WordMode::Whitespace);
+        counter.update(b"abc\xff def\n");
+        let info = counter.finish();
+        assert_eq!(info.num_lines(), 1);
+        assert_eq!(info.num_words(), 2);
+        assert_eq!(info.num_bytes(), 9);
+        assert_eq!(info.num_chars(), 8);
+        assert_eq!(info.max_line_length(), 8);
+    }
+    
+    #[test]
+    fn test_counter_columns() {
+        let config = get_args_from(["wcr", "-lw", "tests/inputs/fox.txt"]).unwrap();
+        let mut counter = new_counter(&config);
+        counter.update("日本語 の\n \n".as_bytes());
+        let info = counter.finish();
+        
+        assert_eq!(info.num_lines(), 2);
+        assert_eq!(info.num_words(), 2);
+        assert_eq!(info.num_bytes(), 16);
+        assert_eq!(info.num_chars(), 0);
+        assert_eq!(info.num_graphemes(), 0);
+        assert_eq!(info.max_line_length(), 0);
+        assert_eq!(info.num_blank(), 0);
+    }
+    
+    #[test]
+    fn test_counter_line_classes() {
+        let mut counter = Counter::new(WordMode::Whitespace)
+            .with_pattern(regex::Regex::new("TODO").unwrap())
+            .with_comment_prefix("//");
+        counter.update(b"// TODO: fix\n\n  \t\r\n    // note\nlet x = 1; // TODO\n/");
+        let info = counter.finish();
+        
+        assert_eq!(info.num_lines(), 6);
+        assert_eq!(info.num_matching(), 2);
+        assert_eq!(info.num_blank(), 2);
+        assert_eq!(info.num_comment(), 2);
+    }
+    
+    #[test]
+    fn test_sum() {
+        let infos = [
+            count(Cursor::new("a b\n")).unwrap(),
+            count(Cursor::new("long line\nc\n")).unwrap(),
+        ];
+        
+        let total: FileInfo = infos.iter().sum();
+        assert_eq!(total.num_lines(), 3);
+        assert_eq!(total.num_words(), 5);
+        assert_eq!(total.num_bytes(), 16);
+        assert_eq!(total.max_line_length(), 9);
+        
+        assert_eq!(infos[0].clone() + infos[1].clone(), total);
+        assert_eq!(infos.into_iter().sum::<FileInfo>(), total);
+    }
+    
+    #[test]
+    fn test_run_to() {
+        let config = get_args_from(["wcr", "--format=csv", "-l", "tests/inputs/fox.txt"]).unwrap();
+        let mut out = vec![];
+        run_to(config, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "lines,filename,type\n1,tests/inputs/fox.txt,file\n");
+        
+        let err = get_args_from(["wcr", "--no-such-option"]).unwrap_err();
+        assert!(err.downcast_ref::<clap::Error>().is_some());
+        assert!(get_args_from(["wcr", "--jobs=0"]).is_err());
+    }
+}
@@ -7,6 +7,11 @@ type TestResult<T> = MyResult<T>;
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const ATLAMAL: &str = "tests/inputs/atlamal.txt";
+const JAPANESE: &str = "tests/inputs/japanese.txt";
+const TABS: &str = "tests/inputs/tabs.txt";
+const FILES0: &str = "tests/inputs/files0.txt";
+const FOX_COPY: &str = "tests/inputs/fox, copy.txt";
+const TODO: &str = "tests/inputs/todo.rs";
 
 fn run(args: &[&str], expected_file: &str) -> TestResult<()> {
     let expected = std::fs::read_to_string(expected_file)?;
@@ -218,6 +223,11 @@ fn atlamal_stdin() -> TestResult<()> {
     run_stdin(&[], ATLAMAL, "tests/expected/atlamal.txt.stdin.out")
 }
 
+#[test]
+fn fox_stdin_lines() -> TestResult<()> {
+    run_stdin(&["--lines"], FOX, "tests/expected/fox.txt.stdin.l.out")
+}
+
 // ##################################################################################
 
 #[test]
@@ -274,3 +284,374 @@ fn all_lines_words_() -> TestResult<()> {
 fn all_words_chars() -> TestResult<()> {
     run(&["--words", "--chars", EMPTY, FOX, ATLAMAL], "tests/expected/all.wm.out")
 }
+
+// ##################################################################################
+
+#[test]
+fn japanese() -> TestResult<()> {
+    run(&[JAPANESE], "tests/expected/japanese.txt.out")
+}
+
+#[test]
+fn japanese_unicode() -> TestResult<()> {
+    run(&["--word-mode", "unicode", JAPANESE], "tests/expected/japanese.txt.unicode.out")
+}
+
+#[test]
+fn japanese_graphemes() -> TestResult<()> {
+    run(&["--graphemes", JAPANESE], "tests/expected/japanese.txt.graphemes.out")
+}
+
+#[test]
+fn japanese_unicode_words_chars_graphemes() -> TestResult<()> {
+    run(&["--word-mode=unicode", "--words", "--chars", "--graphemes", JAPANESE], "tests/expected/japanese.txt.unicode.wm.graphemes.out")
+}
+
+#[test]
+fn japanese_max_line_length() -> TestResult<()> {
+    run(&["-L", JAPANESE], "tests/expected/japanese.txt.L.out")
+}
+
+#[test]
+fn tabs_lines_max_line_length() -> TestResult<()> {
+    run(&["--lines", "--max-line-length", TABS], "tests/expected/tabs.txt.lL.out")
+}
+
+#[test]
+fn all_max_line_length() -> TestResult<()> {
+    run(&["--max-line-length", EMPTY, FOX, ATLAMAL, JAPANESE], "tests/expected/all.L.out")
+}
+
+#[test]
+fn dies_bad_word_mode() -> TestResult<()> {
+    Command::new(cargo::cargo_bin!())
+        .args(["--word-mode", "foo", FOX])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("isn't a valid value"));
+    
+    Ok(())
+}
+
+// ##################################################################################
+
+#[test]
+fn files0_from() -> TestResult<()> {
+    let expected = std::fs::read_to_string("tests/expected/files0.txt.out")?;
+    
+    Command::new(cargo::cargo_bin!())
+        .args(["--files0-from", FILES0])
+        .assert()
+        .success()
+        .stdout(expected)
+        .stderr(predicates::str::contains("tests/inputs/missing.txt: "))
+        .stderr(predicates::str::contains("invalid zero-length file name"));
+    
+    Ok(())
+}
+
+#[test]
+fn files0_from_stdin() -> TestResult<()> {
+    let stdin = std::fs::read(FILES0)?;
+    let expected = std::fs::read_to_string("tests/expected/files0.txt.stdin.out")?;
+    
+    Command::new(cargo::cargo_bin!())
+        .args(["--files0-from=-"])
+        .write_stdin(stdin)
+        .assert()
+        .success()
+        .stdout(expected);
+    
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn files0_from_non_utf8_name() -> TestResult<()> {
+    use std::os::unix::ffi::OsStrExt;
+    
+    // Such names cannot be checked out everywhere, so the fixture is built here.
+    let dir = tempfile::tempdir()?;
+    let name = dir.path().join(std::ffi::OsStr::from_bytes(b"n\xe9.txt"));
+    std::fs::copy(FOX, &name)?;
+    let files0 = dir.path().join("files0");
+    std::fs::write(&files0, [name.as_os_str().as_bytes(), b"\0"].concat())?;
+    
+    Command::new(cargo::cargo_bin!())
+        .args([std::ffi::OsStr::new("--files0-from"), files0.as_os_str()])
+        .assert()
+        .success()
+        .stdout(format!(" 1  9 48 {}\n", name.display()))
+        .stderr("");
+    
+    Ok(())
+}
+
+#[test]
+fn dies_files0_from_with_file_operands() -> TestResult<()> {
+    Command::new(cargo::cargo_bin!())
+        .args(["--files0-from", FILES0, FOX])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("file operands cannot be combined with --files0-from"));
+    
+    Ok(())
+}
+
+// ##################################################################################
+
+#[test]
+fn format_json() -> TestResult<()> {
+    run(&["--format", "json", FOX, FOX_COPY], "tests/expected/fox.json.out")
+}
+
+#[test]
+fn format_json_stdin() -> TestResult<()> {
+    run_stdin(&["--format", "json"], FOX, "tests/expected/fox.txt.stdin.json.out")
+}
+
+#[test]
+fn format_jsonl() -> TestResult<()> {
+    run(&["--format=jsonl", "-lwm", FOX, JAPANESE], "tests/expected/fox_japanese.lwm.jsonl.out")
+}
+
+#[test]
+fn format_csv() -> TestResult<()> {
+    run(&["--format", "csv", FOX, FOX_COPY], "tests/expected/fox.csv.out")
+}
+
+#[test]
+fn format_tsv() -> TestResult<()> {
+    run(&["--format", "tsv", "-L", FOX, ATLAMAL], "tests/expected/fox_atlamal.L.tsv.out")
+}
+
+// ##################################################################################
+
+#[test]
+fn all_jobs() -> TestResult<()> {
+    run(&["--jobs", "3", EMPTY, FOX, ATLAMAL], "tests/expected/all.out")
+}
+
+#[test]
+fn all_lines_words_chars_jobs() -> TestResult<()> {
+    run(&["-j", "2", "--lines", "--words", "--chars", EMPTY, FOX, ATLAMAL], "tests/expected/all.lwm.out")
+}
+
+#[test]
+fn all_bytes_jobs() -> TestResult<()> {
+    run(&["-j", "8", "--bytes", EMPTY, FOX, ATLAMAL], "tests/expected/all.c.out")
+}
+
+#[test]
+fn dies_bad_jobs() -> TestResult<()> {
+    Command::new(cargo::cargo_bin!())
+        .args(["--jobs", "0", FOX])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("invalid number of jobs -- 0"));
+    
+    Ok(())
+}
+
+// ##################################################################################
+
+fn make_tree() -> TestResult<tempfile::TempDir> {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+    std::fs::create_dir_all(root.join("src/nested"))?;
+    std::fs::create_dir_all(root.join("docs"))?;
+    std::fs::write(root.join(".gitignore"), "*.log\n")?;
+    std::fs::write(root.join("Makefile"), "all:\n")?;
+    std::fs::write(root.join("src/main.rs"), "fn main() {\n}\n")?;
+    std::fs::write(root.join("src/nested/lib.rs"), "pub fn f() {}\n")?;
+    std::fs::write(root.join("docs/guide.md"), "# Guide\n\nRead me.\n")?;
+    std::fs::write(root.join("docs/build.log"), "ignored\n")?;
+    Ok(dir)
+}
+
+fn run_in(dir: &tempfile::TempDir, args: &[&str], expected: &str) -> TestResult<()> {
+    Command::new(cargo::cargo_bin!())
+        .current_dir(dir.path())
+        .args(args)
+        .assert()
+        .success()
+        .stdout(expected.to_string());
+    
+    Ok(())
+}
+
+#[test]
+fn recursive() -> TestResult<()> {
+    let dir = make_tree()?;
+    run_in(&dir, &["-r", "-l", "."], concat!(
+        " 1 ./Makefile\n",
+        " 3 ./docs/guide.md\n",
+        " 2 ./src/main.rs\n",
+        " 1 ./src/nested/lib.rs\n",
+        " 7 total\n",
+    ))
+}
+
+#[test]
+fn recursive_no_ignore() -> TestResult<()> {
+    let dir = make_tree()?;
+    run_in(&dir, &["-r", "-l", "--no-ignore", "docs"], concat!(
+        " 1 docs/build.log\n",
+        " 3 docs/guide.md\n",
+        " 4 total\n",
+    ))
+}
+
+#[test]
+fn recursive_include_exclude() -> TestResult<()> {
+    let dir = make_tree()?;
+    run_in(&dir, &["-r", "-c", "--include", "*.rs", "--exclude", "nested", "."], "14 ./src/main.rs\n")
+}
+
+#[test]
+fn recursive_group_by_ext() -> TestResult<()> {
+    let dir = make_tree()?;
+    run_in(&dir, &["-r", "--group-by", "ext", "."], concat!(
+        " 1  1  5 (none)\n",
+        " 3  4 18 *.md\n",
+        " 3  8 28 *.rs\n",
+        " 7 13 51 total\n",
+    ))
+}
+
+#[test]
+fn recursive_group_by_dir() -> TestResult<()> {
+    let dir = make_tree()?;
+    run_in(&dir, &["-r", "-lw", "--group-by=dir", "."], concat!(
+        " 1  1 .\n",
+        " 3  4 ./docs\n",
+        " 3  8 ./src\n",
+        " 7 13 total\n",
+    ))
+}
+
+#[test]
+fn recursive_group_by_ext_csv() -> TestResult<()> {
+    let dir = make_tree()?;
+    run_in(&dir, &["-r", "-l", "--group-by", "ext", "--format=csv", "."], concat!(
+        "lines,filename,type\n",
+        "1,(none),group\n",
+        "3,*.md,group\n",
+        "3,*.rs,group\n",
+        "7,total,total\n",
+    ))
+}
+
+#[test]
+fn dies_bad_glob() -> TestResult<()> {
+    Command::new(cargo::cargo_bin!())
+        .args(["-r", "--include", "[", "."])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("invalid glob \"[\""));
+    
+    Ok(())
+}
+
+// ##################################################################################
+
+#[test]
+fn progress_stdin() -> TestResult<()> {
+    let stdin = std::fs::read_to_string(ATLAMAL)?;
+    let expected = std::fs::read_to_string("tests/expected/atlamal.txt.stdin.out")?;
+    
+    Command::new(cargo::cargo_bin!())
+        .args(["--progress=0"])
+        .write_stdin(stdin)
+        .assert()
+        .success()
+        .stdout(expected)
+        .stderr(predicates::str::contains("lines=1 words=9 bytes=48 ("))
+        .stderr(predicates::str::contains("lines=4 words=29 bytes=177 ("));
+    
+    Ok(())
+}
+
+#[test]
+fn progress_file() -> TestResult<()> {
+    let expected = std::fs::read_to_string("tests/expected/fox.txt.l.out")?;
+    
+    Command::new(cargo::cargo_bin!())
+        .args(["--progress=0", "--lines", FOX])
+        .assert()
+        .success()
+        .stdout(expected)
+        .stderr(predicates::str::starts_with("tests/inputs/fox.txt: lines=1 ("));
+    
+    Ok(())
+}
+
+#[test]
+fn dies_bad_progress_interval() -> TestResult<()> {
+    Command::new(cargo::cargo_bin!())
+        .args(["--progress=-1", FOX])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("invalid progress interval -- -1"));
+    
+    Ok(())
+}
+
+#[test]
+fn dies_progress_jobs() -> TestResult<()> {
+    Command::new(cargo::cargo_bin!())
+        .args(["-j", "2", "--progress=0", FOX])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicates::str::contains("--progress cannot be used with --jobs greater than 1"));
+    
+    Ok(())
+}
+
+// ##################################################################################
+
+#[test]
+fn todo_match_blank_comment() -> TestResult<()> {
+    run(&["--match", "TODO", "--blank", "--comment-prefix", "//", TODO], "tests/expected/todo.rs.match.blank.comment.out")
+}
+
+#[test]
+fn todo_fox_lines_match() -> TestResult<()> {
+    run(&["--lines", "--match", "TODO|Hello", TODO, FOX], "tests/expected/todo_fox.l.match.out")
+}
+
+#[test]
+fn todo_blank_comment_jsonl() -> TestResult<()> {
+    run(&["--format=jsonl", "--blank", "--comment-prefix=//", TODO], "tests/expected/todo.rs.blank.comment.jsonl.out")
+}
+
+#[test]
+fn dies_bad_match() -> TestResult<()> {
+    Command::new(cargo::cargo_bin!())
+        .args(["--match", "(", FOX])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Invalid --match \"(\""));
+    
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn bytes_zero_size_pseudo_file() -> TestResult<()> {
+    // Files in /proc report a size of 0 but still have contents.
+    let filename = "/proc/version";
+    assert_eq!(std::fs::metadata(filename)?.len(), 0);
+    let expected = format!("{} {}\n", std::fs::read(filename)?.len(), filename);
+    
+    for jobs in ["1", "2"] {
+        Command::new(cargo::cargo_bin!())
+            .args(["--bytes", "--jobs", jobs, filename])
+            .assert()
+            .success()
+            .stdout(expected.clone());
+    }
+    
+    Ok(())
+}
@@ -0,0 +1,8 @@
+// TODO: split this module
+
+fn main() {
+    // Print a greeting
+    println!("Hello");   
+      
+    // TODO: read the name from args
+}